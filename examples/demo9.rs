@@ -1,274 +1,520 @@
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    path::PathBuf,
     rc::Rc,
-    sync::{Arc, Mutex},
 };
 
 use fltk::{
     draw::LineStyle,
-    enums::{Align, Color, Event, FrameType},
+    enums::{CallbackTrigger, Color, Event, FrameType, Key, Shortcut},
     prelude::*,
     *,
 };
-use geo::{
-    coord, line_string, point, polygon, Coordinate, EuclideanDistance, Intersects, Line,
-    LineString, Point, Rect,
+use fltk_demo::{
+    i18n,
+    scene::{
+        self, document, measure_line, svg, Align, Cap, Dash, Elem, ElemArrow, ElemBezier, ElemDoc,
+        ElemEllipse, ElemFreehand, ElemId, ElemImage, ElemLine, ElemPolyline, ElemRect, ElemText,
+        FontFamily, Head, Join, Painter, Scene, Status, Style, TextStyle,
+    },
+    watermark,
 };
+use geo::{coord, Coordinate, Rect};
 
 // --
 
-trait Elem {
-    fn draw(&self, hover: bool, scale: f64);
-    fn get_vertex(&self) -> Vec<Coordinate<f64>>;
-    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate);
-    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate);
-    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32);
-    fn hover_condition(&self, mouse_point: Point) -> bool;
+// paints the scene with fltk::draw, `scale` zooms the whole canvas and
+// `origin` is where the frame sits in the window
+struct FltkPainter {
+    scale: f64,
+    origin: (i32, i32),
+    // None when the outline is fully transparent
+    stroke: Option<Color>,
+    fill: Option<Color>,
 }
 
-#[derive(Debug, Copy, Clone)]
-struct ElemLine {
-    from_coord: Coordinate,
-    end_coord: Coordinate,
-}
-
-impl Elem for ElemLine {
-    fn draw(&self, hover: bool, scale: f64) {
-        let line = self;
-        // draw::scale_xy(line.end_coord.x / 2., line.end_coord.y / 2.);
-        draw::set_line_style(LineStyle::Solid, 3);
-        if hover {
-            draw::draw_box(
-                FrameType::OvalBox,
-                (line.from_coord.x - 5.) as i32,
-                (line.from_coord.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
-            draw::draw_box(
-                FrameType::OvalBox,
-                (line.end_coord.x - 5.) as i32,
-                (line.end_coord.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
-            draw::set_draw_color(Color::DarkRed);
-        } else {
-            draw::set_draw_color(Color::Red);
+impl FltkPainter {
+    fn new(scale: f64, origin: (i32, i32)) -> Self {
+        FltkPainter {
+            scale,
+            origin,
+            stroke: Some(Color::Black),
+            fill: None,
         }
-
-        draw::draw_line(
-            line.from_coord.x as i32,
-            line.from_coord.y as i32,
-            line.end_coord.x as i32,
-            line.end_coord.y as i32,
-        );
     }
 
-    fn get_vertex(&self) -> Vec<Coordinate> {
-        vec![self.from_coord, self.end_coord]
+    // scene to window coordinates
+    fn at(&self, c: Coordinate) -> (f64, f64) {
+        (
+            self.origin.0 as f64 + c.x * self.scale,
+            self.origin.1 as f64 + c.y * self.scale,
+        )
     }
 
-    fn hover_condition(&self, mouse_point: Point) -> bool {
-        let t_line = Line::new(self.from_coord, self.end_coord);
-        mouse_point.euclidean_distance(&t_line) < 10.
+    fn xy(&self, c: Coordinate) -> (i32, i32) {
+        let (x, y) = self.at(c);
+        (x as i32, y as i32)
     }
 
-    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
-        self.from_coord = from_coord;
-        self.end_coord = end_coord;
+    fn vertex(&self, c: Coordinate) {
+        let (x, y) = self.at(c);
+        draw::vertex(x, y);
     }
 
-    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
-        let x_dif = end_coord.x - from_coord.x;
-        let y_dif = end_coord.y - from_coord.y;
-
-        self.from_coord.x = self.from_coord.x + x_dif;
-        self.from_coord.y = self.from_coord.y + y_dif;
-        self.end_coord.x = self.end_coord.x + x_dif;
-        self.end_coord.y = self.end_coord.y + y_dif;
+    fn curve_point(&self, c: Coordinate) -> draw::Coord<f64> {
+        let (x, y) = self.at(c);
+        draw::Coord(x, y)
     }
 
-    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
-        match drag_vertex {
-            0 => {
-                self.from_coord = end_coord;
-            }
-            1 => {
-                self.end_coord = end_coord;
+    // picks the stroke color, false when there is nothing to stroke
+    fn begin_outline(&self) -> bool {
+        match self.stroke {
+            Some(color) => {
+                draw::set_draw_color(color);
+                true
             }
-            _ => (),
+            None => false,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct ElemRect {
-    tl_coord: Coordinate, // top left coord
-    width: f64,
-    height: f64,
+// fltk has no alpha when drawing, so translucent colors are mixed with the
+// white canvas
+fn fltk_color(color: scene::Color) -> Color {
+    let a = color[3] as f64 / 255.;
+    let mix = |c: u8| (c as f64 * a + 255. * (1. - a)).round() as u8;
+    Color::from_rgb(mix(color[0]), mix(color[1]), mix(color[2]))
 }
 
-impl Elem for ElemRect {
-    fn draw(&self, hover: bool, scale: f64) {
-        let rect = self;
-        let vec = rect.get_vertex();
-        let [tl, tr, br, bl] = [vec[0], vec[1], vec[2], vec[3]];
-
-        draw::push_matrix();
-        draw::scale_xy(self.tl_coord.x, self.tl_coord.y);
-        draw::scale(scale);
-        draw::translate(self.width * scale, self.width * scale);
-
-        if hover {
-            draw::draw_box(
-                FrameType::OvalBox,
-                (tl.x - 5.) as i32,
-                (tl.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
-            draw::draw_box(
-                FrameType::OvalBox,
-                (tr.x - 5.) as i32,
-                (tr.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
-            draw::draw_box(
-                FrameType::OvalBox,
-                (br.x - 5.) as i32,
-                (br.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
-            draw::draw_box(
-                FrameType::OvalBox,
-                (bl.x - 5.) as i32,
-                (bl.y - 5.) as i32,
-                10,
-                10,
-                Color::DarkRed,
-            );
+fn line_style(style: &Style) -> LineStyle {
+    let dash = match style.dash {
+        Dash::Solid => LineStyle::Solid,
+        Dash::Dash => LineStyle::Dash,
+        Dash::Dot => LineStyle::Dot,
+        Dash::DashDot => LineStyle::DashDot,
+    };
+    let cap = match style.cap {
+        Cap::Butt => LineStyle::CapFlat,
+        Cap::Round => LineStyle::CapRound,
+        Cap::Square => LineStyle::CapSquare,
+    };
+    let join = match style.join {
+        Join::Miter => LineStyle::JoinMiter,
+        Join::Round => LineStyle::JoinRound,
+        Join::Bevel => LineStyle::JoinBevel,
+    };
+    dash | cap | join
+}
+
+fn fltk_font(font: FontFamily) -> enums::Font {
+    match font {
+        FontFamily::Sans => enums::Font::Helvetica,
+        FontFamily::Serif => enums::Font::Times,
+        FontFamily::Mono => enums::Font::Courier,
+    }
+}
 
-            draw::set_draw_color(Color::DarkRed);
+// a window position back to the scene: relative to the frame, which sits
+// below the menu bar, then out of the zoomed view
+fn to_scene(frm: &frame::Frame, scale: f64, (x, y): (i32, i32)) -> Coordinate {
+    coord! {
+        x: (x - frm.x()) as f64 / scale,
+        y: (y - frm.y()) as f64 / scale,
+    }
+}
+
+// the inverse of `to_scene`, for widgets laid over the canvas
+fn to_window(frm: &frame::Frame, scale: f64, c: Coordinate) -> (i32, i32) {
+    (
+        frm.x() + (c.x * scale) as i32,
+        frm.y() + (c.y * scale) as i32,
+    )
+}
+
+impl Painter for FltkPainter {
+    fn set_style(&mut self, style: &Style) {
+        let stroke = style.stroke_color();
+        self.stroke = (stroke[3] > 0).then(|| fltk_color(stroke));
+        self.fill = style.fill_color().map(fltk_color);
+        draw::set_line_style(line_style(style), (style.width * self.scale).round() as i32);
+    }
+
+    fn line(&mut self, from: Coordinate, to: Coordinate) {
+        if !self.begin_outline() {
+            return;
+        }
+        let (x1, y1) = self.xy(from);
+        let (x2, y2) = self.xy(to);
+        draw::draw_line(x1, y1, x2, y2);
+    }
+
+    fn rect(&mut self, tl: Coordinate, width: f64, height: f64) {
+        let (x, y) = self.xy(tl);
+        let (w, h) = ((width * self.scale) as i32, (height * self.scale) as i32);
+        if let Some(fill) = self.fill {
+            draw::draw_rect_fill(x, y, w, h, fill);
+        }
+        if self.begin_outline() {
+            draw::draw_rect(x, y, w, h);
+        }
+    }
+
+    fn ellipse(&mut self, center: Coordinate, rx: f64, ry: f64) {
+        let (x, y) = self.xy(coord! {x: center.x - rx, y: center.y - ry});
+        let w = (rx * 2. * self.scale) as i32;
+        let h = (ry * 2. * self.scale) as i32;
+        if let Some(fill) = self.fill {
+            draw::set_draw_color(fill);
+            draw::draw_pie(x, y, w, h, 0., 360.);
+        }
+        if self.begin_outline() {
+            draw::draw_arc(x, y, w, h, 0., 360.);
+        }
+    }
+
+    fn polyline(&mut self, points: &[Coordinate], closed: bool) {
+        if let (Some(fill), true) = (self.fill, closed) {
+            draw::set_draw_color(fill);
+            draw::begin_complex_polygon();
+            for p in points {
+                self.vertex(*p);
+            }
+            draw::end_complex_polygon();
+        }
+        if !self.begin_outline() {
+            return;
+        }
+        if closed {
+            draw::begin_loop();
+        } else {
+            draw::begin_line();
+        }
+        for p in points {
+            self.vertex(*p);
+        }
+        if closed {
+            draw::end_loop();
         } else {
-            draw::set_draw_color(Color::Red);
+            draw::end_line();
         }
+    }
 
-        draw::set_line_style(LineStyle::Solid, 3);
-        draw::draw_rect(
-            tl.x as i32,
-            tl.y as i32,
-            self.width as i32,
-            self.height as i32,
+    fn quad(&mut self, from: Coordinate, control: Coordinate, to: Coordinate) {
+        // fltk only has cubic curves, raise the degree
+        let c1 = coord! {
+            x: from.x + (control.x - from.x) * 2. / 3.,
+            y: from.y + (control.y - from.y) * 2. / 3.,
+        };
+        let c2 = coord! {
+            x: to.x + (control.x - to.x) * 2. / 3.,
+            y: to.y + (control.y - to.y) * 2. / 3.,
+        };
+        if !self.begin_outline() {
+            return;
+        }
+        draw::begin_line();
+        draw::draw_curve(
+            self.curve_point(from),
+            self.curve_point(c1),
+            self.curve_point(c2),
+            self.curve_point(to),
         );
+        draw::end_line();
+    }
+
+    fn bezier(&mut self, points: &[Coordinate], closed: bool) {
+        let c = |p: Coordinate| self.curve_point(p);
+        if let (Some(fill), true) = (self.fill, closed) {
+            draw::set_draw_color(fill);
+            draw::begin_complex_polygon();
+            for seg in points.windows(4).step_by(3) {
+                draw::draw_curve(c(seg[0]), c(seg[1]), c(seg[2]), c(seg[3]));
+            }
+            draw::end_complex_polygon();
+        }
+        if !self.begin_outline() {
+            return;
+        }
+        if closed {
+            draw::begin_loop();
+        } else {
+            draw::begin_line();
+        }
+        for seg in points.windows(4).step_by(3) {
+            draw::draw_curve(c(seg[0]), c(seg[1]), c(seg[2]), c(seg[3]));
+        }
+        if closed {
+            draw::end_loop();
+        } else {
+            draw::end_line();
+        }
+    }
 
-        draw::pop_matrix();
+    // filled with the stroke color, like arrow heads
+    fn fill_polygon(&mut self, points: &[Coordinate]) {
+        if !self.begin_outline() {
+            return;
+        }
+        draw::begin_polygon();
+        for p in points {
+            self.vertex(*p);
+        }
+        draw::end_polygon();
     }
 
-    fn get_vertex(&self) -> Vec<Coordinate> {
-        let mut tl = coord! {x: self.tl_coord.x, y: self.tl_coord.y};
-        let mut tr = coord! {x: 0., y: 0.};
-        let mut br = coord! {x: self.tl_coord.x + self.width, y: self.tl_coord.y + self.height};
-        let mut bl = coord! {x: 0., y: 0.};
+    // stamped with the bundled font, the one the text box is measured with,
+    // so hover and handles sit on the glyphs
+    fn text(&mut self, text: &ElemText) {
+        let style = &text.style;
+        let (width, _) = text.size();
+        for (i, line) in text.lines().iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let offset = style.align.offset(measure_line(line, style.size), width);
+            let (x, y) = self.at(coord! {
+                x: text.origin.x + offset,
+                y: text.origin.y + i as f64 * text.line_height(),
+            });
+            // the stamp has a one pixel margin
+            let stamp = watermark::text_stamp(line, (style.size * self.scale) as f32, style.color);
+            let (w, h) = (stamp.width() as i32, stamp.height() as i32);
+            if let Ok(mut img) =
+                fltk::image::RgbImage::new(stamp.as_raw(), w, h, enums::ColorDepth::Rgba8)
+            {
+                img.draw(x.round() as i32 - 1, y.round() as i32 - 1, w, h);
+            }
+        }
+    }
 
-        let mut t = 0.;
-        if tl.x > br.x {
-            t = tl.x;
-            tl.x = br.x;
-            br.x = t;
+    fn image(&mut self, image: &ElemImage) {
+        let pixels = match image.pixels() {
+            Some(pixels) => pixels,
+            None => return,
+        };
+        let (x, y) = self.xy(image.bounds.tl_coord);
+        let w = (image.bounds.width * self.scale) as i32;
+        let h = (image.bounds.height * self.scale) as i32;
+        let mut data = pixels.as_raw().clone();
+        if image.opacity < 1. {
+            for px in data.chunks_mut(4) {
+                px[3] = (px[3] as f64 * image.opacity) as u8;
+            }
         }
-        if tl.y > br.y {
-            t = tl.y;
-            tl.y = br.y;
-            br.y = t;
+        let (pw, ph) = (pixels.width() as i32, pixels.height() as i32);
+        if let Ok(mut img) = fltk::image::RgbImage::new(&data, pw, ph, enums::ColorDepth::Rgba8) {
+            img.scale(w, h, false, true);
+            img.draw(x, y, w, h);
         }
+    }
 
-        bl.x = tl.x;
-        bl.y = br.y;
+    fn handle(&mut self, center: Coordinate, color: scene::Color) {
+        let (x, y) = self.xy(center);
+        draw::draw_box(FrameType::OvalBox, x - 5, y - 5, 10, 10, fltk_color(color));
+    }
+}
 
-        tr.x = br.x;
-        tr.y = tl.y;
+fn font_key(font: FontFamily) -> &'static str {
+    match font {
+        FontFamily::Sans => "text.sans",
+        FontFamily::Serif => "text.serif",
+        FontFamily::Mono => "text.mono",
+    }
+}
 
-        Vec::from([tl, tr, br, bl])
+fn align_key(align: Align) -> &'static str {
+    match align {
+        Align::Left => "text.left",
+        Align::Center => "text.center",
+        Align::Right => "text.right",
     }
+}
 
-    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
-        let mut tfrom = from_coord.clone();
-        let mut tend = end_coord.clone();
+const TEXT_SIZES: [u8; 6] = [12, 16, 24, 32, 48, 72];
 
-        if tfrom.x > tend.x {
-            let t = tfrom.x;
-            tfrom.x = tend.x;
-            tend.x = t;
-        }
+const STROKE_WIDTHS: [u8; 5] = [1, 2, 3, 5, 8];
 
-        if tfrom.y > tend.y {
-            let t = tfrom.y;
-            tfrom.y = tend.y;
-            tend.y = t;
-        }
+fn dash_key(dash: Dash) -> &'static str {
+    match dash {
+        Dash::Solid => "style.solid",
+        Dash::Dash => "style.dash",
+        Dash::Dot => "style.dot",
+        Dash::DashDot => "style.dash_dot",
+    }
+}
 
-        self.tl_coord = tfrom;
-        self.width = tend.x - tfrom.x;
-        self.height = tend.y - tfrom.y;
+fn cap_key(cap: Cap) -> &'static str {
+    match cap {
+        Cap::Butt => "style.butt",
+        Cap::Round => "style.round",
+        Cap::Square => "style.square",
     }
+}
 
-    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
-        let x_dif = end_coord.x - from_coord.x;
-        let y_dif = end_coord.y - from_coord.y;
+fn join_key(join: Join) -> &'static str {
+    match join {
+        Join::Miter => "style.miter",
+        Join::Round => "style.round",
+        Join::Bevel => "style.bevel",
+    }
+}
 
-        self.tl_coord.x += x_dif;
-        self.tl_coord.y += y_dif;
+fn head_key(head: Head) -> &'static str {
+    match head {
+        Head::None => "arrow.none",
+        Head::Triangle => "arrow.triangle",
+        Head::Open => "arrow.open",
+        Head::Circle => "arrow.circle",
+        Head::Bar => "arrow.bar",
     }
+}
 
-    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
-        match drag_vertex {
-            0 => {
-                self.width += self.tl_coord.x - end_coord.x;
-                self.height += self.tl_coord.y - end_coord.y;
-                self.tl_coord = end_coord;
-            }
-            1 => {
-                self.width = end_coord.x - self.tl_coord.x;
-                self.height += self.tl_coord.y - end_coord.y;
-                self.tl_coord.y = end_coord.y;
-            }
-            2 => {
-                self.width = end_coord.x - self.tl_coord.x;
-                self.height = end_coord.y - self.tl_coord.y;
-            }
-            3 => {
-                self.height = end_coord.y - self.tl_coord.y;
-                self.width += self.tl_coord.x - end_coord.x;
-                self.tl_coord.x = end_coord.x;
-            }
-            _ => (),
-        }
-        if self.width < 0. {
-            self.width = 0.;
-            self.tl_coord.x = end_coord.x;
+fn kind_key(elem: &dyn Elem) -> &'static str {
+    match elem.kind() {
+        "line" => "draw.line",
+        "rect" => "draw.rect",
+        "ellipse" => "draw.ellipse",
+        "arrow" => "draw.arrow",
+        "freehand" => "draw.pen",
+        "polyline" => "draw.polyline",
+        "polygon" => "draw.polygon",
+        "text" => "draw.text",
+        "bezier" => "draw.path",
+        "image" => "draw.image",
+        _ => "inspector.none",
+    }
+}
+
+// --
+
+// the side panel with the inspected element's box and style; every field
+// sends an event that edits the element
+struct Inspector {
+    panel: group::Flex,
+    kind: frame::Frame,
+    // x, y, width, height
+    bounds: Vec<valuator::ValueInput>,
+    stroke: button::Button,
+    fill_on: button::CheckButton,
+    fill: button::Button,
+    width: valuator::ValueInput,
+    dash: menu::Choice,
+}
+
+impl Inspector {
+    fn new(s: app::Sender<EventFn>) -> Self {
+        let mut panel = group::Flex::default().column();
+        panel.set_margin(6);
+        let kind = frame::Frame::default();
+        panel.set_size(&kind, 30);
+        // a label on the left, the field on the right
+        let row = |panel: &mut group::Flex, key: &'static str| {
+            let row = group::Flex::default().row();
+            frame::Frame::default()
+                .with_label(i18n::tr(key))
+                .with_align(enums::Align::Left | enums::Align::Inside);
+            panel.set_size(&row, 26);
+            row
+        };
+        let number = |panel: &mut group::Flex, key, step: f64, event| {
+            let row = row(panel, key);
+            let mut input = valuator::ValueInput::default();
+            input.set_bounds(-10000., 10000.);
+            input.set_step(step, 1);
+            // one change, and one undo step, per Enter or focus change
+            // rather than per keystroke
+            input.set_trigger(CallbackTrigger::EnterKeyAlways | CallbackTrigger::Release);
+            input.emit(s, event);
+            row.end();
+            input
+        };
+        let bounds = vec![
+            number(&mut panel, "inspector.x", 1., EventFn::InspectBounds),
+            number(&mut panel, "inspector.y", 1., EventFn::InspectBounds),
+            number(&mut panel, "inspector.width", 1., EventFn::InspectBounds),
+            number(&mut panel, "inspector.height", 1., EventFn::InspectBounds),
+        ];
+        let width = number(
+            &mut panel,
+            "inspector.line_width",
+            0.5,
+            EventFn::InspectWidth,
+        );
+
+        let stroke_row = row(&mut panel, "inspector.stroke");
+        let mut stroke = button::Button::default();
+        stroke.emit(s, EventFn::InspectStroke);
+        stroke_row.end();
+
+        let fill_row = group::Flex::default().row();
+        let mut fill_on = button::CheckButton::default().with_label(i18n::tr("inspector.fill"));
+        fill_on.emit(s, EventFn::InspectFill);
+        let mut fill = button::Button::default();
+        fill.emit(s, EventFn::InspectFillColor);
+        fill_row.end();
+        panel.set_size(&fill_row, 26);
+
+        let dash_row = row(&mut panel, "inspector.dash");
+        let mut dash = menu::Choice::default();
+        for d in Dash::ALL {
+            dash.add_choice(i18n::tr(dash_key(d)));
         }
-        if self.height < 0. {
-            self.height = 0.;
-            self.tl_coord.y = end_coord.y;
+        dash.emit(s, EventFn::InspectDash);
+        dash_row.end();
+
+        // the rest of the column stays empty
+        frame::Frame::default();
+        panel.end();
+
+        let mut inspector = Inspector {
+            panel,
+            kind,
+            bounds,
+            stroke,
+            fill_on,
+            fill,
+            width,
+            dash,
+        };
+        inspector.show(None);
+        inspector
+    }
+
+    fn set_active<W: WidgetExt>(widget: &mut W, active: bool) {
+        if active {
+            widget.activate();
+        } else {
+            widget.deactivate();
         }
     }
 
-    fn hover_condition(&self, mouse_point: Point) -> bool {
-        let vertex = self.get_vertex();
-        Rect::new(vertex[0], vertex[2]).intersects(&mouse_point)
-            || point! {vertex[0]}.euclidean_distance(&mouse_point) < 10.
-            || point! {vertex[1]}.euclidean_distance(&mouse_point) < 10.
-            || point! {vertex[2]}.euclidean_distance(&mouse_point) < 10.
-            || point! {vertex[3]}.euclidean_distance(&mouse_point) < 10.
+    // fills the fields from `elem`, or greys them out
+    fn show(&mut self, elem: Option<(&dyn Elem, Rect)>) {
+        let kind = elem.map_or("inspector.none", |(elem, _)| kind_key(elem));
+        self.kind.set_label(i18n::tr(kind));
+        let values = elem.map_or([0.; 4], |(_, b)| {
+            [b.min().x, b.min().y, b.width(), b.height()]
+        });
+        for (input, value) in self.bounds.iter_mut().zip(values) {
+            input.set_value(value);
+            Self::set_active(input, elem.is_some());
+        }
+        let style = elem.and_then(|(elem, _)| elem.style().copied());
+        let active = style.is_some();
+        let style = style.unwrap_or_default();
+        self.width.set_value(style.width);
+        self.stroke.set_color(fltk_color(style.stroke));
+        self.fill_on.set_checked(style.fill.is_some());
+        self.fill
+            .set_color(fltk_color(style.fill.unwrap_or([0xff; 4])));
+        self.dash
+            .set_value(Dash::ALL.iter().position(|d| *d == style.dash).unwrap_or(0) as i32);
+        Self::set_active(&mut self.width, active);
+        Self::set_active(&mut self.stroke, active);
+        Self::set_active(&mut self.fill_on, active);
+        Self::set_active(&mut self.fill, active && style.fill.is_some());
+        Self::set_active(&mut self.dash, active);
+        self.panel.redraw();
     }
 }
 
@@ -278,59 +524,423 @@ impl Elem for ElemRect {
 enum EventFn {
     ClickLineBtn,
     ClickRectBtn,
+    ClickEllipseBtn,
+    ClickArrowBtn,
+    ClickPenBtn,
+    ClickPolylineBtn,
+    ClickPolygonBtn,
+    ClickPathBtn,
+    ClickTextBtn,
+    ClickImageBtn,
+    EmbedImages,
+    ImageOpacity(u8),
+    // open the text editor at a canvas position
+    EditText(f64, f64),
+    CommitText,
+    CancelText,
+    TextFont(FontFamily),
+    TextSize(u8),
+    TextAlign(Align),
+    TextColor,
+    StrokeColor,
+    StrokeWidth(u8),
+    StrokeDash(Dash),
+    StrokeCap(Cap),
+    StrokeJoin(Join),
+    FillColor,
+    NoFill,
+    Opacity(u8),
+    // the selection may have changed
+    Inspect,
+    InspectBounds,
+    InspectStroke,
+    InspectFill,
+    InspectFillColor,
+    InspectWidth,
+    InspectDash,
+    FinishShape,
+    Delete,
+    Deselect,
+    BringToFront,
+    SendToBack,
+    SmoothPen,
+    StartHead(Head),
+    EndHead(Head),
     ClickRemoveBtn,
     ClickScaleBtn,
-}
-
-#[derive(Clone)]
-enum Status {
-    CREATING,
-    EDIT_MOVING, // default
-    EDIT_RESIZING,
-    DELETING,
+    Open,
+    Save,
+    SaveAs,
+    ExportSvg,
+    ImportSvg,
+    Undo,
+    Redo,
 }
 
 struct AppView {
     app: app::App,
     win: window::Window,
     frm: frame::Frame,
-    draw_elems: Rc<RefCell<Vec<Box<dyn Elem>>>>,
-    hover_index: Rc<RefCell<i32>>,
-    drag_vertex: Rc<RefCell<i32>>,
-    status: Rc<RefCell<Status>>,
-    eventReceiver: app::Receiver<EventFn>,
+    scene: Rc<RefCell<Scene>>,
+    event_receiver: app::Receiver<EventFn>,
+    sender: app::Sender<EventFn>,
     scale: Rc<RefCell<f64>>,
+    // where Save writes without asking
+    path: Option<PathBuf>,
+    heads: (Head, Head),
+    smooth_pen: bool,
+    // the next canvas click places a text
+    text_tool: Rc<Cell<bool>>,
+    text_style: TextStyle,
+    editor: input::MultilineInput,
+    // the text being edited (None for a new one) and its origin
+    editing: Option<(Option<ElemId>, Coordinate)>,
+    // new images: kept in the document or linked by path
+    embed_images: bool,
+    image_opacity: f64,
+    // given to every new shape
+    style: Style,
+    inspector: Inspector,
+    // the element in the inspector, the last one selected
+    inspected: Option<ElemId>,
 }
 
 impl AppView {
     pub fn new() -> Self {
         let app = app::App::default();
         let (s, receiver) = app::channel();
-        let mut main_win = window::Window::default().with_size(500, 600);
+        let mut main_win = window::Window::default().with_size(680, 630);
+
+        let mut root_col = group::Flex::new(0, 0, 680, 630, None).column();
 
-        let mut root_col = group::Flex::new(0, 0, 500, 600, None).column();
+        let mut menu = menu::MenuBar::default();
+        let file_item = |key| format!("{}/{}", i18n::tr("file.menu"), i18n::tr(key));
+        menu.add_emit(
+            &file_item("file.open"),
+            Shortcut::Ctrl | 'o',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Open,
+        );
+        menu.add_emit(
+            &file_item("file.save"),
+            Shortcut::Ctrl | 's',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Save,
+        );
+        menu.add_emit(
+            &file_item("file.save_as"),
+            Shortcut::Ctrl | Shortcut::Shift | 's',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::SaveAs,
+        );
+        menu.add_emit(
+            &file_item("file.export_svg"),
+            Shortcut::Ctrl | 'e',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::ExportSvg,
+        );
+        menu.add_emit(
+            &file_item("file.import_svg"),
+            Shortcut::Ctrl | 'i',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::ImportSvg,
+        );
+        let edit_item = |key| format!("{}/{}", i18n::tr("edit.menu"), i18n::tr(key));
+        menu.add_emit(
+            &edit_item("edit.undo"),
+            Shortcut::Ctrl | 'z',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Undo,
+        );
+        menu.add_emit(
+            &edit_item("edit.redo"),
+            Shortcut::Ctrl | Shortcut::Shift | 'z',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Redo,
+        );
+        menu.add_emit(
+            &edit_item("edit.finish_shape"),
+            Shortcut::from_key(Key::Enter),
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::FinishShape,
+        );
+        menu.add_emit(
+            &edit_item("edit.delete"),
+            Shortcut::from_key(Key::Delete),
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Delete,
+        );
+        menu.add_emit(
+            &edit_item("edit.deselect"),
+            Shortcut::from_key(Key::Escape),
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::Deselect,
+        );
+        menu.add_emit(
+            &edit_item("edit.bring_to_front"),
+            Shortcut::Ctrl | ']',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::BringToFront,
+        );
+        menu.add_emit(
+            &edit_item("edit.send_to_back"),
+            Shortcut::Ctrl | '[',
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::SendToBack,
+        );
 
-        let top_col = group::Flex::default().row();
+        let mut top_col = group::Flex::default().row();
         let mut frm = frame::Frame::default();
         frm.set_frame(FrameType::FlatBox);
         frm.set_color(Color::White);
+        let inspector = Inspector::new(s);
+        top_col.set_size(&inspector.panel, 180);
         top_col.end();
 
-        let btm_col = group::Flex::default().row();
-        let mut line_btn = button::Button::default().with_label("Line");
+        // shapes on the first row, everything else on the second
+        let btm_col = group::Flex::default().column();
+        let shape_row = group::Flex::default().row();
+        let mut line_btn = button::Button::default().with_label(i18n::tr("draw.line"));
         line_btn.emit(s, EventFn::ClickLineBtn);
-        let mut rect_btn = button::Button::default().with_label("Rect");
+        let mut rect_btn = button::Button::default().with_label(i18n::tr("draw.rect"));
         rect_btn.emit(s, EventFn::ClickRectBtn);
-        let mut remove_btn = button::Button::default().with_label("Remove");
+        let mut ellipse_btn = button::Button::default().with_label(i18n::tr("draw.ellipse"));
+        ellipse_btn.emit(s, EventFn::ClickEllipseBtn);
+        ellipse_btn.set_tooltip(i18n::tr("draw.ellipse_tip"));
+        let mut arrow_btn = button::Button::default().with_label(i18n::tr("draw.arrow"));
+        arrow_btn.emit(s, EventFn::ClickArrowBtn);
+        arrow_btn.set_tooltip(i18n::tr("draw.arrow_tip"));
+        let mut pen_btn = button::Button::default().with_label(i18n::tr("draw.pen"));
+        pen_btn.emit(s, EventFn::ClickPenBtn);
+        let mut polyline_btn = button::Button::default().with_label(i18n::tr("draw.polyline"));
+        polyline_btn.emit(s, EventFn::ClickPolylineBtn);
+        polyline_btn.set_tooltip(i18n::tr("draw.polyline_tip"));
+        let mut polygon_btn = button::Button::default().with_label(i18n::tr("draw.polygon"));
+        polygon_btn.emit(s, EventFn::ClickPolygonBtn);
+        polygon_btn.set_tooltip(i18n::tr("draw.polyline_tip"));
+        let mut path_btn = button::Button::default().with_label(i18n::tr("draw.path"));
+        path_btn.emit(s, EventFn::ClickPathBtn);
+        path_btn.set_tooltip(i18n::tr("draw.path_tip"));
+        shape_row.end();
+        let tool_row = group::Flex::default().row();
+        let mut text_btn = button::Button::default().with_label(i18n::tr("draw.text"));
+        text_btn.emit(s, EventFn::ClickTextBtn);
+        text_btn.set_tooltip(i18n::tr("draw.text_tip"));
+        let mut image_btn = button::Button::default().with_label(i18n::tr("draw.image"));
+        image_btn.emit(s, EventFn::ClickImageBtn);
+        image_btn.set_tooltip(i18n::tr("draw.image_tip"));
+        let mut remove_btn = button::Button::default().with_label(i18n::tr("draw.remove"));
         remove_btn.emit(s, EventFn::ClickRemoveBtn);
-        let mut scale_btn = button::Button::default().with_label("Scale");
+        let mut scale_btn = button::Button::default().with_label(i18n::tr("draw.scale"));
         scale_btn.emit(s, EventFn::ClickScaleBtn);
+        tool_row.end();
         btm_col.end();
 
+        // heads used by the next arrows
+        for (key, default, event) in [
+            (
+                "arrow.start",
+                Head::None,
+                EventFn::StartHead as fn(Head) -> EventFn,
+            ),
+            ("arrow.end", Head::Triangle, EventFn::EndHead),
+        ] {
+            for head in Head::ALL {
+                let label = format!(
+                    "{}/{}/{}",
+                    i18n::tr("arrow.menu"),
+                    i18n::tr(key),
+                    i18n::tr(head_key(head))
+                );
+                let idx = menu.add_emit(
+                    &label,
+                    Shortcut::None,
+                    menu::MenuFlag::Radio,
+                    s,
+                    event(head),
+                );
+                if head == default {
+                    if let Some(mut item) = menu.at(idx) {
+                        item.set();
+                    }
+                }
+            }
+        }
+
+        menu.add_emit(
+            &format!("{}/{}", i18n::tr("pen.menu"), i18n::tr("pen.smooth")),
+            Shortcut::None,
+            menu::MenuFlag::Toggle,
+            s,
+            EventFn::SmoothPen,
+        );
+
+        let text_item = |key: &'static str, value: &str| {
+            format!("{}/{}/{}", i18n::tr("text.menu"), i18n::tr(key), value)
+        };
+        let default_style = TextStyle::default();
+        let mut add_radio = |label: &str, event: EventFn, set: bool| {
+            let idx = menu.add_emit(label, Shortcut::None, menu::MenuFlag::Radio, s, event);
+            if set {
+                if let Some(mut item) = menu.at(idx) {
+                    item.set();
+                }
+            }
+        };
+        for font in FontFamily::ALL {
+            add_radio(
+                &text_item("text.font", i18n::tr(font_key(font))),
+                EventFn::TextFont(font),
+                font == default_style.font,
+            );
+        }
+        for size in TEXT_SIZES {
+            add_radio(
+                &text_item("text.size", &size.to_string()),
+                EventFn::TextSize(size),
+                size as f64 == default_style.size,
+            );
+        }
+        for align in Align::ALL {
+            add_radio(
+                &text_item("text.align", i18n::tr(align_key(align))),
+                EventFn::TextAlign(align),
+                align == default_style.align,
+            );
+        }
+        menu.add_emit(
+            &format!("{}/{}", i18n::tr("text.menu"), i18n::tr("text.color")),
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            s,
+            EventFn::TextColor,
+        );
+
+        let image_item = |key| format!("{}/{}", i18n::tr("image.menu"), i18n::tr(key));
+        let idx = menu.add_emit(
+            &image_item("image.embed"),
+            Shortcut::None,
+            menu::MenuFlag::Toggle,
+            s,
+            EventFn::EmbedImages,
+        );
+        if let Some(mut item) = menu.at(idx) {
+            item.set();
+        }
+        for opacity in [100, 75, 50, 25] {
+            let idx = menu.add_emit(
+                &format!("{}/{}%", image_item("image.opacity"), opacity),
+                Shortcut::None,
+                menu::MenuFlag::Radio,
+                s,
+                EventFn::ImageOpacity(opacity),
+            );
+            if opacity == 100 {
+                if let Some(mut item) = menu.at(idx) {
+                    item.set();
+                }
+            }
+        }
+
+        let style_item = |key: &'static str, value: &str| {
+            format!("{}/{}/{}", i18n::tr("style.menu"), i18n::tr(key), value)
+        };
+        let new_style = Style::default();
+        let mut add_radio = |label: &str, event: EventFn, set: bool| {
+            let idx = menu.add_emit(label, Shortcut::None, menu::MenuFlag::Radio, s, event);
+            if set {
+                if let Some(mut item) = menu.at(idx) {
+                    item.set();
+                }
+            }
+        };
+        for width in STROKE_WIDTHS {
+            add_radio(
+                &style_item("style.width", &width.to_string()),
+                EventFn::StrokeWidth(width),
+                width as f64 == new_style.width,
+            );
+        }
+        for dash in Dash::ALL {
+            add_radio(
+                &style_item("style.dashes", i18n::tr(dash_key(dash))),
+                EventFn::StrokeDash(dash),
+                dash == new_style.dash,
+            );
+        }
+        for cap in Cap::ALL {
+            add_radio(
+                &style_item("style.cap", i18n::tr(cap_key(cap))),
+                EventFn::StrokeCap(cap),
+                cap == new_style.cap,
+            );
+        }
+        for join in Join::ALL {
+            add_radio(
+                &style_item("style.join", i18n::tr(join_key(join))),
+                EventFn::StrokeJoin(join),
+                join == new_style.join,
+            );
+        }
+        for opacity in [100, 75, 50, 25] {
+            add_radio(
+                &style_item("style.opacity", &format!("{}%", opacity)),
+                EventFn::Opacity(opacity),
+                opacity == 100,
+            );
+        }
+        let style_entry = |key| format!("{}/{}", i18n::tr("style.menu"), i18n::tr(key));
+        for (key, event) in [
+            ("style.stroke_color", EventFn::StrokeColor),
+            ("style.fill_color", EventFn::FillColor),
+            ("style.no_fill", EventFn::NoFill),
+        ] {
+            menu.add_emit(
+                &style_entry(key),
+                Shortcut::None,
+                menu::MenuFlag::Normal,
+                s,
+                event,
+            );
+        }
+
+        root_col.set_size(&menu, 30);
         root_col.set_size(&top_col, 500);
         root_col.set_size(&btm_col, 100);
         root_col.end();
 
+        // floats over the canvas while a text is edited
+        let mut editor = input::MultilineInput::default();
+        editor.set_frame(FrameType::BorderBox);
+        editor.hide();
+        editor.handle(move |_, e| match e {
+            Event::KeyDown if app::event_key() == Key::Escape => {
+                s.send(EventFn::CancelText);
+                true
+            }
+            Event::KeyDown if app::event_key() == Key::Enter && app::is_event_ctrl() => {
+                s.send(EventFn::CommitText);
+                true
+            }
+            Event::Unfocus => {
+                s.send(EventFn::CommitText);
+                false
+            }
+            _ => false,
+        });
+
         main_win.end();
         main_win.show();
 
@@ -338,173 +948,590 @@ impl AppView {
             app,
             win: main_win,
             frm,
-            draw_elems: Rc::new(RefCell::new(Vec::new())),
-            eventReceiver: receiver,
-            hover_index: Rc::new(RefCell::new(0)),
-            drag_vertex: Rc::new(RefCell::new(0)),
+            scene: Rc::new(RefCell::new(Scene::new())),
+            event_receiver: receiver,
+            sender: s,
             scale: Rc::new(RefCell::new(1.)),
-            status: Rc::new(RefCell::new(Status::EDIT_MOVING)),
+            path: None,
+            heads: (Head::None, Head::Triangle),
+            smooth_pen: false,
+            text_tool: Rc::new(Cell::new(false)),
+            text_style: TextStyle::default(),
+            editor,
+            editing: None,
+            embed_images: true,
+            image_opacity: 1.,
+            style: Style::default(),
+            inspector,
+            inspected: None,
         }
     }
 
+    // a new shape in the current style
+    fn start_creating(&mut self, mut elem: Box<dyn Elem>) {
+        if let Some(style) = elem.style_mut() {
+            *style = self.style;
+        }
+        self.scene.borrow_mut().start_creating(elem);
+    }
+
     fn click_line_btn(&mut self) {
-        let line = ElemLine {
-            from_coord: coord! {x: 0., y: 0.},
-            end_coord: coord! {x: 0., y: 0.},
-        };
-        self.draw_elems.borrow_mut().push(Box::new(line));
-        *self.status.borrow_mut() = Status::CREATING;
+        let line = ElemLine::new(coord! {x: 0., y: 0.}, coord! {x: 0., y: 0.});
+        self.start_creating(Box::new(line));
     }
 
     fn click_rect_btn(&mut self) {
-        self.draw_elems.borrow_mut().push(Box::new(ElemRect {
-            tl_coord: coord! {x: 0., y: 0.},
-            width: 0.,
-            height: 0.,
-        }));
-        *self.status.borrow_mut() = Status::CREATING;
+        let rect = ElemRect::new(coord! {x: 0., y: 0.}, 0., 0.);
+        self.start_creating(Box::new(rect));
+    }
+
+    fn click_ellipse_btn(&mut self) {
+        let ellipse = ElemEllipse::new(coord! {x: 0., y: 0.}, 0., 0.);
+        self.start_creating(Box::new(ellipse));
+    }
+
+    fn click_arrow_btn(&mut self) {
+        let (start, end) = self.heads;
+        let arrow =
+            ElemArrow::new(coord! {x: 0., y: 0.}, coord! {x: 0., y: 0.}).with_heads(start, end);
+        self.start_creating(Box::new(arrow));
+    }
+
+    fn click_pen_btn(&mut self) {
+        let pen = ElemFreehand::new(Vec::new(), self.smooth_pen);
+        self.start_creating(Box::new(pen));
+    }
+
+    fn click_polyline_btn(&mut self, closed: bool) {
+        let poly = ElemPolyline::placed(closed);
+        self.start_creating(Box::new(poly));
+    }
+
+    fn click_path_btn(&mut self) {
+        let path = ElemBezier::placed();
+        self.start_creating(Box::new(path));
+    }
+
+    fn click_text_btn(&mut self) {
+        self.text_tool.set(true);
     }
 
+    // edits the text under `coord`, or starts a new one there
+    fn edit_text(&mut self, coord: Coordinate) {
+        if self.editing.is_some() {
+            self.commit_text();
+        }
+        let existing = {
+            let scene = self.scene.borrow();
+            scene
+                .hit_test(coord.into())
+                .and_then(|id| scene.get(id).map(|elem| (id, elem)))
+                // only a text is worth turning into a document here
+                .filter(|(_, elem)| elem.text().is_some())
+                .map(|(id, elem)| (id, elem.to_doc()))
+        };
+        let (id, text, origin, style) = match existing {
+            Some((
+                id,
+                ElemDoc::Text {
+                    origin,
+                    text,
+                    text_style,
+                },
+            )) => (Some(id), text, document::from_xy(origin), text_style),
+            _ => (None, String::new(), coord, self.text_style),
+        };
+
+        let s = *self.scale.borrow();
+        let size = (style.size * s).round() as i32;
+        // room for a few more characters and the frame
+        let (w, h) = ElemText::new(origin, &text, style).size();
+        let (x, y) = to_window(&self.frm, s, origin);
+        self.editor.resize(
+            x,
+            y,
+            (((w + style.size * 2.) * s) as i32).max(200),
+            ((h + style.size * 0.5) * s) as i32,
+        );
+        self.editor.set_text_font(fltk_font(style.font));
+        self.editor.set_text_size(size);
+        self.editor.set_text_color(fltk_color(style.color));
+        self.editor.set_value(&text);
+        self.editor.show();
+        let _ = self.editor.take_focus();
+        self.editing = Some((id, origin));
+        self.win.redraw();
+    }
+
+    fn commit_text(&mut self) {
+        let (id, origin) = match self.editing.take() {
+            Some(editing) => editing,
+            None => return,
+        };
+        let text = self.editor.value();
+        self.editor.hide();
+        let mut scene = self.scene.borrow_mut();
+        match id {
+            Some(id) if text.trim().is_empty() => {
+                scene.delete(id);
+            }
+            Some(id) => {
+                scene.edit(id, |elem| {
+                    elem.set_text(&text);
+                });
+            }
+            None if text.trim().is_empty() => (),
+            None => {
+                scene.add(Box::new(ElemText::new(origin, &text, self.text_style)));
+            }
+        }
+        self.win.redraw();
+    }
+
+    fn cancel_text(&mut self) {
+        self.editing = None;
+        self.editor.hide();
+        self.win.redraw();
+    }
+
+    fn choose_text_color(&mut self) {
+        if let Some(color) = Self::choose_color(i18n::tr("text.color"), self.text_style.color) {
+            self.text_style.color = color;
+        }
+    }
+
+    // `current` without its alpha as the default, the answer opaque
+    fn choose_color(title: &str, current: scene::Color) -> Option<scene::Color> {
+        let [r, g, b, _] = current;
+        dialog::color_chooser_with_default(title, dialog::ColorMode::Byte, (r, g, b))
+            .map(|(r, g, b)| [r, g, b, 0xff])
+    }
+
+    // the Style menu: new shapes and the selected ones
+    fn set_style(&mut self, f: impl Fn(&mut Style)) {
+        f(&mut self.style);
+        self.edit_style(f);
+    }
+
+    fn choose_stroke_color(&mut self) {
+        if let Some(color) = Self::choose_color(i18n::tr("style.stroke_color"), self.style.stroke) {
+            self.set_style(|style| style.stroke = color);
+        }
+    }
+
+    fn choose_fill_color(&mut self) {
+        let current = self.style.fill.unwrap_or([0xff, 0xff, 0xff, 0xff]);
+        if let Some(color) = Self::choose_color(i18n::tr("style.fill_color"), current) {
+            self.set_style(|style| style.fill = Some(color));
+        }
+    }
+
+    // shows the last selected element
+    fn refresh_inspector(&mut self) {
+        let scene = self.scene.borrow();
+        self.inspected = scene.selection().last().copied();
+        let shown = self
+            .inspected
+            .and_then(|id| Some((scene.get(id)?, scene.bounds(id)?)));
+        self.inspector.show(shown);
+    }
+
+    fn inspect_bounds(&mut self) {
+        let id = match self.inspected {
+            Some(id) => id,
+            None => return,
+        };
+        let v: Vec<f64> = self.inspector.bounds.iter().map(|i| i.value()).collect();
+        let to = Rect::new(
+            coord! {x: v[0], y: v[1]},
+            coord! {x: v[0] + v[2], y: v[1] + v[3]},
+        );
+        if self.scene.borrow_mut().set_bounds(id, to) {
+            self.frm.redraw();
+        }
+    }
+
+    // one undo step on the style of every selected element
+    fn edit_style(&mut self, f: impl Fn(&mut Style)) {
+        let edited = self.scene.borrow_mut().edit_selection(|elem| {
+            if let Some(style) = elem.style_mut() {
+                f(style);
+            }
+        });
+        if edited {
+            self.frm.redraw();
+        }
+    }
+
+    fn inspected_style(&self) -> Option<Style> {
+        let scene = self.scene.borrow();
+        scene.get(self.inspected?)?.style().copied()
+    }
+
+    fn inspect_stroke(&mut self) {
+        let current = match self.inspected_style() {
+            Some(style) => style.stroke,
+            None => return,
+        };
+        if let Some(color) = Self::choose_color(i18n::tr("style.stroke_color"), current) {
+            self.edit_style(|style| style.stroke = color);
+        }
+    }
+
+    fn inspect_fill(&mut self) {
+        let fill = self.inspector.fill_on.is_checked().then(|| [0xff; 4]);
+        self.edit_style(|style| style.fill = fill);
+    }
+
+    fn inspect_fill_color(&mut self) {
+        let current = match self.inspected_style().and_then(|style| style.fill) {
+            Some(fill) => fill,
+            None => return,
+        };
+        if let Some(color) = Self::choose_color(i18n::tr("style.fill_color"), current) {
+            self.edit_style(|style| style.fill = Some(color));
+        }
+    }
+
+    fn inspect_width(&mut self) {
+        let width = self.inspector.width.value().max(0.);
+        self.edit_style(|style| style.width = width);
+    }
+
+    fn inspect_dash(&mut self) {
+        if let Some(dash) = Dash::ALL.get(self.inspector.dash.value() as usize).copied() {
+            self.edit_style(|style| style.dash = dash);
+        }
+    }
+
+    // centered on the canvas, shrunk when it doesn't fit
+    fn click_image_btn(&mut self) {
+        let path = match self.choose_file(false, i18n::tr("image.filter"), "png") {
+            Some(path) => path,
+            None => return,
+        };
+        let origin = coord! {x: 0., y: 0.};
+        let loaded = if self.embed_images {
+            std::fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| {
+                    ElemImage::embedded(origin, &bytes).map_err(|err| err.to_string())
+                })
+        } else {
+            ElemImage::linked(origin, &path).map_err(|err| err.to_string())
+        };
+        let mut image = match loaded {
+            Ok(image) => image,
+            Err(err) => {
+                dialog::alert_default(&format!("{}: {}", i18n::tr("image.open_failed"), err));
+                return;
+            }
+        };
+        self.sync_canvas_size();
+        let mut scene = self.scene.borrow_mut();
+        let (w, h) = scene.canvas_size();
+        let (w, h) = (w as f64, h as f64);
+        if image.bounds.width > w * 0.8 || image.bounds.height > h * 0.8 {
+            image.fit(w * 0.8, h * 0.8);
+        }
+        image.bounds.tl_coord = coord! {
+            x: (w - image.bounds.width) / 2.,
+            y: (h - image.bounds.height) / 2.,
+        };
+        image.opacity = self.image_opacity;
+        scene.add(Box::new(image));
+        self.frm.redraw();
+    }
+
+    fn finish_shape(&mut self) {
+        self.scene.borrow_mut().finish_placing();
+        self.frm.redraw();
+    }
+
+    // the vertex under the pointer, otherwise the selection
+    fn delete(&mut self) {
+        let mut scene = self.scene.borrow_mut();
+        let pointer = scene.pointer();
+        if scene.delete_vertex_at(pointer) || scene.delete_selection() {
+            self.frm.redraw();
+        }
+    }
+
+    fn deselect(&mut self) {
+        if self.scene.borrow_mut().clear_selection() {
+            self.frm.redraw();
+        }
+    }
+
+    fn restack(&mut self, front: bool) {
+        if self.scene.borrow_mut().restack_selection(front) {
+            self.frm.redraw();
+        }
+    }
+
+    // removes the selection, or the next clicked element when nothing is
+    // selected
     fn click_remove_btn(&mut self) {
-        *self.status.borrow_mut() = Status::DELETING;
+        let mut scene = self.scene.borrow_mut();
+        if scene.delete_selection() {
+            self.frm.redraw();
+        } else {
+            scene.start_deleting();
+        }
     }
+
     fn click_scale_btn(&mut self) {
         (*self.scale.borrow_mut()) += 0.1;
         self.frm.redraw();
     }
+
+    fn choose_file(&self, save: bool, filter: &str, ext: &str) -> Option<PathBuf> {
+        let mut chooser = dialog::NativeFileChooser::new(if save {
+            dialog::NativeFileChooserType::BrowseSaveFile
+        } else {
+            dialog::NativeFileChooserType::BrowseFile
+        });
+        chooser.set_filter(filter);
+        if save {
+            chooser.set_option(dialog::NativeFileChooserOptions::SaveAsConfirm);
+        }
+        chooser.show();
+        let path = chooser.filename();
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        if save && path.extension().is_none() {
+            return Some(path.with_extension(ext));
+        }
+        Some(path)
+    }
+
+    fn open(&mut self) {
+        let path = match self.choose_file(false, i18n::tr("file.filter"), "json") {
+            Some(path) => path,
+            None => return,
+        };
+        match document::load(&path) {
+            Ok(scene) => {
+                *self.scene.borrow_mut() = scene;
+                self.path = Some(path);
+                self.frm.redraw();
+            }
+            Err(err) => {
+                dialog::alert_default(&format!("{}: {}", i18n::tr("file.open_failed"), err))
+            }
+        }
+    }
+
+    // the canvas size written to files follows the frame
+    fn sync_canvas_size(&mut self) {
+        let s = *self.scale.borrow();
+        self.scene.borrow_mut().set_canvas_size(
+            (self.frm.w() as f64 / s) as u32,
+            (self.frm.h() as f64 / s) as u32,
+        );
+    }
+
+    fn save_to(&mut self, path: PathBuf) {
+        self.sync_canvas_size();
+        let scene = self.scene.borrow();
+        match document::save(&scene, &path) {
+            Ok(()) => self.path = Some(path),
+            Err(err) => {
+                dialog::alert_default(&format!("{}: {}", i18n::tr("file.save_failed"), err))
+            }
+        }
+    }
+
+    fn save(&mut self) {
+        match self.path.clone() {
+            Some(path) => self.save_to(path),
+            None => self.save_as(),
+        }
+    }
+
+    fn save_as(&mut self) {
+        if let Some(path) = self.choose_file(true, i18n::tr("file.filter"), "json") {
+            self.save_to(path);
+        }
+    }
+
+    fn export_svg(&mut self) {
+        let path = match self.choose_file(true, i18n::tr("file.svg_filter"), "svg") {
+            Some(path) => path,
+            None => return,
+        };
+        self.sync_canvas_size();
+        let text = svg::to_svg(&self.scene.borrow());
+        if let Err(err) = std::fs::write(&path, text) {
+            dialog::alert_default(&format!("{}: {}", i18n::tr("file.save_failed"), err));
+        }
+    }
+
+    // adds the shapes of an SVG file on top of the current drawing
+    fn import_svg(&mut self) {
+        let path = match self.choose_file(false, i18n::tr("file.svg_filter"), "svg") {
+            Some(path) => path,
+            None => return,
+        };
+        let imported = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| svg::import(&text).map_err(|err| err.to_string()));
+        match imported {
+            Ok(imported) => {
+                let mut scene = self.scene.borrow_mut();
+                for (_, elem) in imported.scene.iter() {
+                    scene.add(elem.box_clone());
+                }
+                self.frm.redraw();
+                if !imported.warnings.is_empty() {
+                    dialog::message_default(&format!(
+                        "{}\n\n{}",
+                        i18n::tr("file.import_warnings"),
+                        imported.warnings.join("\n")
+                    ));
+                }
+            }
+            Err(err) => {
+                dialog::alert_default(&format!("{}: {}", i18n::tr("file.open_failed"), err))
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.scene.borrow_mut().undo() {
+            self.frm.redraw();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.scene.borrow_mut().redo() {
+            self.frm.redraw();
+        }
+    }
+
     pub fn run(&mut self) {
         self.frm.draw({
-            let draw_elems = Rc::clone(&self.draw_elems);
-            let hover_index = Rc::clone(&self.hover_index);
+            let scene = Rc::clone(&self.scene);
             let scale = Rc::clone(&self.scale);
             move |frm| {
-                for (i, elem) in draw_elems.borrow_mut().iter_mut().enumerate() {
-                    elem.draw(i as i32 == *hover_index.borrow(), *scale.borrow());
-                }
+                let mut painter = FltkPainter::new(*scale.borrow(), (frm.x(), frm.y()));
+                draw::push_clip(frm.x(), frm.y(), frm.w(), frm.h());
+                scene.borrow().paint(&mut painter);
+                draw::pop_clip();
             }
         });
         self.frm.handle({
-            let draw_elems = Rc::clone(&self.draw_elems);
-            let hover_index = Rc::clone(&self.hover_index);
-            let drag_vertex = Rc::clone(&self.drag_vertex);
-            let status = Rc::clone(&self.status);
-            let mut tx = 0;
-            let mut ty = 0;
+            let scene = Rc::clone(&self.scene);
+            let scale = Rc::clone(&self.scale);
+            let text_tool = Rc::clone(&self.text_tool);
+            let sender = self.sender;
             move |frm, e| -> bool {
-                let (x, y) = app::event_coords();
-                let mouse_point = point! {
-                    x: x as f64,
-                    y: y as f64
-                };
+                let coord = to_scene(frm, *scale.borrow(), app::event_coords());
+                let mut scene = scene.borrow_mut();
+                scene.set_constrained(app::is_event_shift());
                 match e {
                     Event::Push => {
-                        tx = x;
-                        ty = y;
-                        let idx = *hover_index.borrow();
-                        let mut elems = draw_elems.borrow_mut();
-
-                        let mut status = status.borrow_mut();
-                        let elem = elems.get_mut(idx as usize);
-
-                        if let Some(elem) = elem {
-                            match *status {
-                                Status::DELETING => {
-                                    if idx > -1 {
-                                        elems.remove(idx as usize);
-                                        *status = Status::EDIT_MOVING;
-                                    }
-                                }
-                                Status::EDIT_MOVING => {
-                                    let vertex = elem.get_vertex();
-
-                                    for (i, coord) in vertex.iter().enumerate() {
-                                        let point = Point::new(coord.x, coord.y);
-                                        if mouse_point.euclidean_distance(&point) < 10. {
-                                            *status = Status::EDIT_RESIZING;
-                                            *drag_vertex.borrow_mut() = i as i32;
-                                            *status = Status::EDIT_RESIZING;
-                                        }
-                                    }
-                                }
-                                Status::CREATING => (),
-                                Status::EDIT_RESIZING => (),
-                            };
+                        let on_text = scene
+                            .hit_test(coord.into())
+                            .and_then(|id| scene.get(id))
+                            .is_some_and(|elem| elem.text().is_some());
+                        if text_tool.replace(false) || (app::event_clicks() && on_text) {
+                            sender.send(EventFn::EditText(coord.x, coord.y));
+                        } else if app::event_mouse_button() == app::MouseButton::Right {
+                            scene.delete_vertex_at(coord);
+                        } else if app::event_clicks() && scene.status() == Status::Placing {
+                            scene.finish_placing();
+                        } else {
+                            scene.pointer_press(coord);
                         }
-
+                        frm.redraw();
+                        sender.send(EventFn::Inspect);
                         true
                     }
                     Event::Drag => {
-                        match *status.borrow() {
-                            Status::CREATING => {
-                                let mut elems = draw_elems.borrow_mut();
-                                let top = elems.last_mut();
-                                if let Some(elem) = top {
-                                    elem.creating(
-                                        coord! {x: tx as f64, y: ty as f64},
-                                        coord! {x: x  as f64, y: y as f64},
-                                    )
-                                }
-                            }
-                            Status::EDIT_MOVING => {
-                                let idx = *hover_index.borrow_mut();
-                                let mut elems = draw_elems.borrow_mut();
-                                let elem = elems.get_mut(idx as usize);
-                                if let Some(elem) = elem {
-                                    elem.edit_moving(
-                                        coord! {x: tx as f64, y: ty as f64},
-                                        coord! {x: x  as f64, y: y as f64},
-                                    );
-                                    tx = x;
-                                    ty = y;
-                                }
-                            }
-                            Status::EDIT_RESIZING => {
-                                let idx = *hover_index.borrow_mut();
-                                let mut elems = draw_elems.borrow_mut();
-                                let elem = elems.get_mut(idx as usize);
-                                if let Some(elem) = elem {
-                                    elem.edit_resizing(
-                                        coord! {x: tx as f64, y: ty as f64},
-                                        coord! {x: x  as f64, y: y as f64},
-                                        *drag_vertex.borrow(),
-                                    );
-                                }
-                            }
-                            Status::DELETING => (),
-                        }
-
+                        scene.pointer_drag(coord);
                         frm.redraw();
-
                         true
                     }
                     Event::Released => {
-                        *status.borrow_mut() = Status::EDIT_MOVING;
+                        scene.pointer_release();
+                        sender.send(EventFn::Inspect);
                         true
                     }
                     Event::Move => {
-                        *hover_index.borrow_mut() = -1;
-                        let len = draw_elems.borrow_mut().len();
-                        for (i, elem) in draw_elems.borrow_mut().iter().rev().enumerate() {
-                            if elem.hover_condition(mouse_point) {
-                                *hover_index.borrow_mut() = (len - i - 1) as i32;
-                                break;
-                            }
+                        if scene.pointer_move(coord) {
+                            frm.redraw();
                         }
-                        frm.redraw();
                         true
                     }
-
                     _ => false,
                 }
             }
         });
         while self.app.wait() {
-            if let Some(msg) = self.eventReceiver.recv() {
+            if let Some(msg) = self.event_receiver.recv() {
+                // typed fields are not rewritten under the cursor
+                let typed = matches!(msg, EventFn::InspectBounds | EventFn::InspectWidth);
                 match msg {
                     EventFn::ClickLineBtn => self.click_line_btn(),
                     EventFn::ClickRectBtn => self.click_rect_btn(),
+                    EventFn::ClickEllipseBtn => self.click_ellipse_btn(),
+                    EventFn::ClickArrowBtn => self.click_arrow_btn(),
+                    EventFn::ClickPenBtn => self.click_pen_btn(),
+                    EventFn::ClickPolylineBtn => self.click_polyline_btn(false),
+                    EventFn::ClickPolygonBtn => self.click_polyline_btn(true),
+                    EventFn::ClickPathBtn => self.click_path_btn(),
+                    EventFn::ClickTextBtn => self.click_text_btn(),
+                    EventFn::ClickImageBtn => self.click_image_btn(),
+                    EventFn::EmbedImages => self.embed_images = !self.embed_images,
+                    EventFn::ImageOpacity(percent) => self.image_opacity = percent as f64 / 100.,
+                    EventFn::EditText(x, y) => self.edit_text(coord! {x: x, y: y}),
+                    EventFn::CommitText => self.commit_text(),
+                    EventFn::CancelText => self.cancel_text(),
+                    EventFn::TextFont(font) => self.text_style.font = font,
+                    EventFn::TextSize(size) => self.text_style.size = size as f64,
+                    EventFn::TextAlign(align) => self.text_style.align = align,
+                    EventFn::TextColor => self.choose_text_color(),
+                    EventFn::StrokeColor => self.choose_stroke_color(),
+                    EventFn::StrokeWidth(width) => {
+                        self.set_style(|style| style.width = width as f64)
+                    }
+                    EventFn::StrokeDash(dash) => self.set_style(|style| style.dash = dash),
+                    EventFn::StrokeCap(cap) => self.set_style(|style| style.cap = cap),
+                    EventFn::StrokeJoin(join) => self.set_style(|style| style.join = join),
+                    EventFn::FillColor => self.choose_fill_color(),
+                    EventFn::NoFill => self.set_style(|style| style.fill = None),
+                    EventFn::Opacity(percent) => {
+                        self.set_style(|style| style.opacity = percent as f64 / 100.)
+                    }
+                    EventFn::Inspect => (),
+                    EventFn::InspectBounds => self.inspect_bounds(),
+                    EventFn::InspectStroke => self.inspect_stroke(),
+                    EventFn::InspectFill => self.inspect_fill(),
+                    EventFn::InspectFillColor => self.inspect_fill_color(),
+                    EventFn::InspectWidth => self.inspect_width(),
+                    EventFn::InspectDash => self.inspect_dash(),
+                    EventFn::FinishShape => self.finish_shape(),
+                    EventFn::Delete => self.delete(),
+                    EventFn::Deselect => self.deselect(),
+                    EventFn::BringToFront => self.restack(true),
+                    EventFn::SendToBack => self.restack(false),
+                    EventFn::SmoothPen => self.smooth_pen = !self.smooth_pen,
+                    EventFn::StartHead(head) => self.heads.0 = head,
+                    EventFn::EndHead(head) => self.heads.1 = head,
                     EventFn::ClickRemoveBtn => self.click_remove_btn(),
                     EventFn::ClickScaleBtn => self.click_scale_btn(),
+                    EventFn::Open => self.open(),
+                    EventFn::Save => self.save(),
+                    EventFn::SaveAs => self.save_as(),
+                    EventFn::ExportSvg => self.export_svg(),
+                    EventFn::ImportSvg => self.import_svg(),
+                    EventFn::Undo => self.undo(),
+                    EventFn::Redo => self.redo(),
+                }
+                if !typed {
+                    self.refresh_inspector();
                 }
             }
         }
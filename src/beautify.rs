@@ -0,0 +1,247 @@
+use image::{GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+use imageproc::filter::gaussian_blur_f32;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Background {
+    Solid {
+        color: [u8; 4],
+    },
+    // angle in degrees, 0 runs left to right, 90 top to bottom
+    Gradient {
+        from: [u8; 4],
+        to: [u8; 4],
+        angle: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Shadow {
+    pub offset: (i32, i32),
+    pub blur: f32,
+    pub color: [u8; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Beautify {
+    pub padding: u32,
+    pub corner_radius: u32,
+    pub background: Background,
+    pub shadow: Option<Shadow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BeautifyPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub options: Beautify,
+}
+
+impl Default for Beautify {
+    fn default() -> Self {
+        Beautify {
+            padding: 48,
+            corner_radius: 10,
+            background: Background::Gradient {
+                from: [0x8e, 0xc5, 0xfc, 0xff],
+                to: [0xe0, 0xc3, 0xfc, 0xff],
+                angle: 45.,
+            },
+            shadow: Some(Shadow {
+                offset: (0, 8),
+                blur: 12.,
+                color: [0, 0, 0, 0x70],
+            }),
+        }
+    }
+}
+
+impl BeautifyPreset {
+    pub fn builtin() -> Vec<BeautifyPreset> {
+        vec![
+            BeautifyPreset {
+                name: String::from("docs"),
+                options: Beautify::default(),
+            },
+            BeautifyPreset {
+                name: String::from("plain"),
+                options: Beautify {
+                    padding: 24,
+                    corner_radius: 0,
+                    background: Background::Solid {
+                        color: [0xff, 0xff, 0xff, 0xff],
+                    },
+                    shadow: Some(Shadow {
+                        offset: (0, 2),
+                        blur: 6.,
+                        color: [0, 0, 0, 0x50],
+                    }),
+                },
+            },
+        ]
+    }
+}
+
+// src-over blend of `src` scaled by `coverage` onto `dst`
+fn blend(dst: &mut Rgba<u8>, src: [u8; 4], coverage: f32) {
+    let sa = src[3] as f32 / 255. * coverage.clamp(0., 1.);
+    if sa <= 0. {
+        return;
+    }
+    let da = dst[3] as f32 / 255.;
+    let oa = sa + da * (1. - sa);
+    for i in 0..3 {
+        let c = (src[i] as f32 * sa + dst[i] as f32 * da * (1. - sa)) / oa;
+        dst[i] = c.round() as u8;
+    }
+    dst[3] = (oa * 255.).round() as u8;
+}
+
+// composites onto an opaque color for formats without alpha, instead of
+// dropping the alpha channel and exposing whatever is under it
+pub fn flatten(img: &RgbaImage, color: [u8; 3]) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let mut px = Rgba([color[0], color[1], color[2], 0xff]);
+        blend(&mut px, img.get_pixel(x, y).0, 1.);
+        Rgb([px[0], px[1], px[2]])
+    })
+}
+
+fn background_at(background: &Background, x: u32, y: u32, w: u32, h: u32) -> [u8; 4] {
+    match *background {
+        Background::Solid { color } => color,
+        Background::Gradient { from, to, angle } => {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            // project onto the gradient direction, normalized over the image corners
+            let project = |x: f32, y: f32| x * cos + y * sin;
+            let corners = [
+                project(0., 0.),
+                project(w as f32, 0.),
+                project(0., h as f32),
+                project(w as f32, h as f32),
+            ];
+            let min = corners.iter().cloned().fold(f32::MAX, f32::min);
+            let max = corners.iter().cloned().fold(f32::MIN, f32::max);
+            let t = if max > min {
+                (project(x as f32 + 0.5, y as f32 + 0.5) - min) / (max - min)
+            } else {
+                0.
+            };
+            let mut out = [0u8; 4];
+            for i in 0..4 {
+                out[i] = (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8;
+            }
+            out
+        }
+    }
+}
+
+// coverage mask of a w*h rectangle with rounded corners, anti-aliased on the arc
+pub fn rounded_mask(w: u32, h: u32, radius: u32) -> GrayImage {
+    let r = radius.min(w / 2).min(h / 2) as f32;
+    GrayImage::from_fn(w, h, |x, y| {
+        if r <= 0. {
+            return Luma([255]);
+        }
+        let px = x as f32 + 0.5;
+        let py = y as f32 + 0.5;
+        let cx = px.clamp(r, w as f32 - r);
+        let cy = py.clamp(r, h as f32 - r);
+        let d = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+        let coverage = (r - d + 0.5).clamp(0., 1.);
+        Luma([(coverage * 255.).round() as u8])
+    })
+}
+
+pub fn beautify(img: &RgbaImage, options: &Beautify) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    let pad = options.padding;
+    let (ow, oh) = (w + pad * 2, h + pad * 2);
+
+    let mut out = RgbaImage::from_fn(ow, oh, |x, y| {
+        Rgba(background_at(&options.background, x, y, ow, oh))
+    });
+    let mask = rounded_mask(w, h, options.corner_radius);
+
+    if let Some(shadow) = options.shadow {
+        let mut shadow_mask = GrayImage::new(ow, oh);
+        for (x, y, m) in mask.enumerate_pixels() {
+            let sx = (x + pad) as i32 + shadow.offset.0;
+            let sy = (y + pad) as i32 + shadow.offset.1;
+            if sx >= 0 && sy >= 0 && (sx as u32) < ow && (sy as u32) < oh {
+                shadow_mask.put_pixel(sx as u32, sy as u32, *m);
+            }
+        }
+        if shadow.blur > 0. {
+            shadow_mask = gaussian_blur_f32(&shadow_mask, shadow.blur);
+        }
+        for (x, y, m) in shadow_mask.enumerate_pixels() {
+            blend(out.get_pixel_mut(x, y), shadow.color, m[0] as f32 / 255.);
+        }
+    }
+
+    for (x, y, px) in img.enumerate_pixels() {
+        let coverage = mask.get_pixel(x, y)[0] as f32 / 255.;
+        blend(out.get_pixel_mut(x + pad, y + pad), px.0, coverage);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    // a capture stand-in with hard edges and a transparent quarter
+    fn sample() -> RgbaImage {
+        RgbaImage::from_fn(64, 40, |x, y| match (x < 32, y < 20) {
+            (true, true) => Rgba([0xd0, 0x30, 0x30, 0xff]),
+            (false, true) => Rgba([0x30, 0x90, 0x30, 0xff]),
+            (true, false) => Rgba([0x30, 0x30, 0xd0, 0xff]),
+            (false, false) => Rgba([0, 0, 0, 0]),
+        })
+    }
+
+    #[test]
+    fn pads_the_capture() {
+        let out = beautify(&sample(), &Beautify::default());
+        assert_eq!(out.dimensions(), (64 + 96, 40 + 96));
+        // the inside of the capture is untouched
+        assert_eq!(
+            out.get_pixel(48 + 16, 48 + 10),
+            &Rgba([0xd0, 0x30, 0x30, 0xff])
+        );
+    }
+
+    #[test]
+    fn builtin_presets_match_golden() {
+        for preset in BeautifyPreset::builtin() {
+            let out = beautify(&sample(), &preset.options);
+            golden::check(&format!("beautify_{}", preset.name), &out);
+        }
+    }
+
+    #[test]
+    fn rounded_mask_cuts_the_corners() {
+        let mask = rounded_mask(20, 20, 6);
+        assert_eq!(mask.get_pixel(0, 0)[0], 0);
+        assert_eq!(mask.get_pixel(10, 0)[0], 255);
+        assert_eq!(mask.get_pixel(10, 10)[0], 255);
+    }
+
+    #[test]
+    fn flatten_composites_onto_the_color() {
+        let img = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([0, 0, 0, 0x80])
+            }
+        });
+        let out = flatten(&img, [0xff, 0xff, 0xff]);
+        assert_eq!(out.get_pixel(0, 0), &Rgb([0xff, 0xff, 0xff]));
+        assert_eq!(out.get_pixel(1, 0), &Rgb([0x7f, 0x7f, 0x7f]));
+    }
+}
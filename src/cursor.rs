@@ -0,0 +1,268 @@
+use image::{Rgba, RgbaImage};
+
+// everything is in native display pixels
+#[derive(Debug, Clone)]
+pub struct CursorImage {
+    pub image: RgbaImage,
+    pub hotspot: (i32, i32),
+    pub position: (i32, i32),
+}
+
+pub trait CursorSource {
+    // `scale` is native pixels per point of the captured display
+    fn cursor(&self, scale: f64) -> Option<CursorImage>;
+}
+
+// draws the cursor onto a frame whose top left corner sits at `origin` on the display
+pub fn composite_cursor(frame: &mut RgbaImage, cursor: &CursorImage, origin: (i32, i32)) {
+    let left = cursor.position.0 - cursor.hotspot.0 - origin.0;
+    let top = cursor.position.1 - cursor.hotspot.1 - origin.1;
+    let (fw, fh) = frame.dimensions();
+
+    for (x, y, src) in cursor.image.enumerate_pixels() {
+        let fx = left + x as i32;
+        let fy = top + y as i32;
+        if fx < 0 || fy < 0 || fx as u32 >= fw || fy as u32 >= fh {
+            continue;
+        }
+        let dst = frame.get_pixel_mut(fx as u32, fy as u32);
+        let a = src[3] as u32;
+        for i in 0..3 {
+            dst[i] = ((src[i] as u32 * a + dst[i] as u32 * (255 - a)) / 255) as u8;
+        }
+        dst[3] = (a + dst[3] as u32 * (255 - a) / 255) as u8;
+    }
+}
+
+// a fixed arrow, for tests and for platforms without a cursor api
+pub struct SyntheticCursor {
+    pub position: (i32, i32),
+}
+
+impl SyntheticCursor {
+    // a 11x16 triangle, black outline and white fill, hotspot at the tip
+    pub fn arrow() -> RgbaImage {
+        RgbaImage::from_fn(11, 16, |x, y| {
+            let right = y * 10 / 15;
+            if x > right {
+                Rgba([0, 0, 0, 0])
+            } else if x == 0 || x == right || y == 15 {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+}
+
+impl CursorSource for SyntheticCursor {
+    fn cursor(&self, _scale: f64) -> Option<CursorImage> {
+        Some(CursorImage {
+            image: SyntheticCursor::arrow(),
+            hotspot: (0, 0),
+            position: self.position,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use self::macos::MacCursor;
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use core_graphics::{
+        event::CGEvent,
+        event_source::{CGEventSource, CGEventSourceStateID},
+    };
+    use image::imageops::{self, FilterType};
+    use objc::{class, msg_send, runtime::Object, sel, sel_impl};
+
+    use super::{CursorImage, CursorSource};
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct NSPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct NSSize {
+        width: f64,
+        height: f64,
+    }
+
+    pub struct MacCursor;
+
+    impl CursorSource for MacCursor {
+        fn cursor(&self, scale: f64) -> Option<CursorImage> {
+            let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).ok()?;
+            let location = CGEvent::new(source).ok()?.location();
+
+            let (tiff, size, hot) = unsafe {
+                let cursor: *mut Object = msg_send![class!(NSCursor), currentSystemCursor];
+                if cursor.is_null() {
+                    return None;
+                }
+                let image: *mut Object = msg_send![cursor, image];
+                let size: NSSize = msg_send![image, size];
+                let hot: NSPoint = msg_send![cursor, hotSpot];
+                let data: *mut Object = msg_send![image, TIFFRepresentation];
+                if data.is_null() {
+                    return None;
+                }
+                let len: usize = msg_send![data, length];
+                let bytes: *const u8 = msg_send![data, bytes];
+                (std::slice::from_raw_parts(bytes, len).to_vec(), size, hot)
+            };
+
+            // the tiff holds the largest representation, bring it to the display scale
+            let image = image::load_from_memory(&tiff).ok()?.to_rgba8();
+            let w = (size.width * scale).round().max(1.) as u32;
+            let h = (size.height * scale).round().max(1.) as u32;
+            let image = imageops::resize(&image, w, h, FilterType::Triangle);
+
+            Some(CursorImage {
+                image,
+                hotspot: ((hot.x * scale) as i32, (hot.y * scale) as i32),
+                position: ((location.x * scale) as i32, (location.y * scale) as i32),
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::linux::XFixesCursor;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::x11::{xfixes, xlib};
+    use image::{Rgba, RgbaImage};
+
+    use super::{CursorImage, CursorSource};
+
+    pub struct XFixesCursor;
+
+    impl CursorSource for XFixesCursor {
+        // X11 already reports native pixels
+        fn cursor(&self, _scale: f64) -> Option<CursorImage> {
+            unsafe {
+                let display = xlib::XOpenDisplay(std::ptr::null());
+                if display.is_null() {
+                    return None;
+                }
+                let raw = xfixes::XFixesGetCursorImage(display);
+                if raw.is_null() {
+                    xlib::XCloseDisplay(display);
+                    return None;
+                }
+                let c = &*raw;
+                let (w, h) = (c.width as u32, c.height as u32);
+                // one premultiplied argb pixel per c_ulong
+                let pixels = std::slice::from_raw_parts(c.pixels, (w * h) as usize);
+                let image = RgbaImage::from_fn(w, h, |x, y| {
+                    let p = pixels[(y * w + x) as usize] as u32;
+                    let a = p >> 24;
+                    let un = |c: u32| (c * 255).checked_div(a).map_or(0, |v| v.min(255) as u8);
+                    Rgba([un(p >> 16 & 0xff), un(p >> 8 & 0xff), un(p & 0xff), a as u8])
+                });
+                let cursor = CursorImage {
+                    image,
+                    hotspot: (c.xhot as i32, c.yhot as i32),
+                    position: (c.x as i32, c.y as i32),
+                };
+                xlib::XFree(raw as *mut _);
+                xlib::XCloseDisplay(display);
+                Some(cursor)
+            }
+        }
+    }
+}
+
+pub fn system_cursor_source() -> Option<Box<dyn CursorSource>> {
+    #[cfg(target_os = "macos")]
+    return Some(Box::new(MacCursor));
+    #[cfg(target_os = "linux")]
+    return Some(Box::new(XFixesCursor));
+    #[allow(unreachable_code)]
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREY: Rgba<u8> = Rgba([0x80, 0x80, 0x80, 0xff]);
+
+    fn frame() -> RgbaImage {
+        RgbaImage::from_pixel(40, 30, GREY)
+    }
+
+    #[test]
+    fn tip_lands_on_the_position() {
+        let cursor = SyntheticCursor { position: (12, 7) }.cursor(1.).unwrap();
+        let mut frame = frame();
+        composite_cursor(&mut frame, &cursor, (0, 0));
+        // the tip is a black outline pixel, the one left of it is untouched
+        assert_eq!(frame.get_pixel(12, 7), &Rgba([0, 0, 0, 255]));
+        assert_eq!(frame.get_pixel(11, 7), &GREY);
+        assert_eq!(frame.get_pixel(12, 6), &GREY);
+        // white fill inside the arrow, transparent pixels keep the frame
+        assert_eq!(frame.get_pixel(13, 7 + 8), &Rgba([255, 255, 255, 255]));
+        assert_eq!(frame.get_pixel(12 + 10, 7), &GREY);
+    }
+
+    #[test]
+    fn hotspot_shifts_the_image() {
+        let mut cursor = SyntheticCursor { position: (20, 20) }.cursor(1.).unwrap();
+        cursor.hotspot = (5, 10);
+        let mut frame = frame();
+        composite_cursor(&mut frame, &cursor, (0, 0));
+        // the image's top left corner is at position - hotspot
+        assert_eq!(frame.get_pixel(15, 10), &Rgba([0, 0, 0, 255]));
+        assert_eq!(frame.get_pixel(15, 9), &GREY);
+    }
+
+    #[test]
+    fn origin_is_subtracted() {
+        // a frame captured from (100, 50) on the display
+        let cursor = SyntheticCursor {
+            position: (105, 60),
+        }
+        .cursor(1.)
+        .unwrap();
+        let mut frame = frame();
+        composite_cursor(&mut frame, &cursor, (100, 50));
+        assert_eq!(frame.get_pixel(5, 10), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn clips_at_the_frame_edges() {
+        let mut frame = frame();
+        let cursor = SyntheticCursor { position: (35, -4) }.cursor(1.).unwrap();
+        composite_cursor(&mut frame, &cursor, (0, 0));
+        // row 4 of the arrow is the top row of the frame
+        assert_eq!(frame.get_pixel(35, 0), &Rgba([0, 0, 0, 255]));
+        let outside = SyntheticCursor {
+            position: (-50, -50),
+        }
+        .cursor(1.)
+        .unwrap();
+        let before = frame.clone();
+        composite_cursor(&mut frame, &outside, (0, 0));
+        assert_eq!(frame, before);
+    }
+
+    #[test]
+    fn blends_translucent_pixels() {
+        let cursor = CursorImage {
+            image: RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 0x80])),
+            hotspot: (0, 0),
+            position: (3, 3),
+        };
+        let mut frame = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 0xff]));
+        composite_cursor(&mut frame, &cursor, (0, 0));
+        assert_eq!(frame.get_pixel(3, 3), &Rgba([0x80, 0x80, 0x80, 0xff]));
+    }
+}
@@ -0,0 +1,304 @@
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::{
+    drawing::draw_hollow_rect_mut,
+    rect::Rect,
+    region_labelling::{connected_components, Connectivity},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    // largest per-channel difference still counted as equal
+    pub threshold: u8,
+    // changed regions smaller than this many pixels are ignored
+    pub min_region_area: u32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            threshold: 16,
+            min_region_area: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // changed pixels inside the box
+    pub area: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffResult {
+    // only the pixels of `regions`
+    pub mask: GrayImage,
+    // pixels inside `regions`
+    pub changed_pixels: u64,
+    // changed pixels dropped with regions below `min_region_area`
+    pub ignored_pixels: u64,
+    pub regions: Vec<Region>,
+}
+
+impl DiffResult {
+    pub fn changed_ratio(&self) -> f64 {
+        let total = self.mask.width() as u64 * self.mask.height() as u64;
+        if total == 0 {
+            0.
+        } else {
+            self.changed_pixels as f64 / total as f64
+        }
+    }
+}
+
+// pads both images with transparent pixels to the larger width and height
+pub fn align(a: &RgbaImage, b: &RgbaImage) -> (RgbaImage, RgbaImage) {
+    let w = a.width().max(b.width());
+    let h = a.height().max(b.height());
+    let pad = |img: &RgbaImage| {
+        RgbaImage::from_fn(w, h, |x, y| {
+            if x < img.width() && y < img.height() {
+                *img.get_pixel(x, y)
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    };
+    (pad(a), pad(b))
+}
+
+pub fn diff(a: &RgbaImage, b: &RgbaImage, options: &DiffOptions) -> DiffResult {
+    let (a, b) = align(a, b);
+    let raw = GrayImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let delta = (0..4)
+            .map(|i| (pa[i] as i16 - pb[i] as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap_or(0);
+        if delta > options.threshold {
+            Luma([255])
+        } else {
+            Luma([0])
+        }
+    });
+
+    let labels = connected_components(&raw, Connectivity::Eight, Luma([0]));
+    // label -> (min x, min y, max x, max y, area)
+    let mut boxes: Vec<(u32, u32, u32, u32, u32)> = Vec::new();
+    for (x, y, label) in labels.enumerate_pixels() {
+        let label = label[0] as usize;
+        if label == 0 {
+            continue;
+        }
+        if boxes.len() < label {
+            boxes.resize(label, (u32::MAX, u32::MAX, 0, 0, 0));
+        }
+        let b = &mut boxes[label - 1];
+        b.0 = b.0.min(x);
+        b.1 = b.1.min(y);
+        b.2 = b.2.max(x);
+        b.3 = b.3.max(y);
+        b.4 += 1;
+    }
+    let kept = |label: u32| label > 0 && boxes[label as usize - 1].4 >= options.min_region_area;
+    let mask = GrayImage::from_fn(a.width(), a.height(), |x, y| {
+        Luma([if kept(labels.get_pixel(x, y)[0]) {
+            255
+        } else {
+            0
+        }])
+    });
+    let total: u64 = boxes.iter().map(|b| b.4 as u64).sum();
+    let regions: Vec<Region> = boxes
+        .iter()
+        .filter(|b| b.4 > 0 && b.4 >= options.min_region_area)
+        .map(|&(x0, y0, x1, y1, area)| Region {
+            x: x0,
+            y: y0,
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+            area,
+        })
+        .collect();
+    let changed_pixels: u64 = regions.iter().map(|r| r.area as u64).sum();
+
+    DiffResult {
+        mask,
+        changed_pixels,
+        ignored_pixels: total - changed_pixels,
+        regions,
+    }
+}
+
+// dims `base`, paints changed pixels red and outlines every region
+pub fn overlay(base: &RgbaImage, result: &DiffResult) -> RgbaImage {
+    let (w, h) = result.mask.dimensions();
+    let mut out = RgbaImage::from_fn(w, h, |x, y| {
+        if result.mask.get_pixel(x, y)[0] > 0 {
+            return Rgba([255, 0, 0, 255]);
+        }
+        if x < base.width() && y < base.height() {
+            let p = base.get_pixel(x, y);
+            let dim = |c: u8| (c as u16 * 2 / 5 + 153) as u8;
+            Rgba([dim(p[0]), dim(p[1]), dim(p[2]), 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    for region in &result.regions {
+        let rect = Rect::at(region.x as i32 - 2, region.y as i32 - 2)
+            .of_size(region.width + 4, region.height + 4);
+        draw_hollow_rect_mut(&mut out, rect, Rgba([255, 0, 255, 255]));
+    }
+    out
+}
+
+// `a` left of column `split`, `b` from there on
+pub fn swipe(a: &RgbaImage, b: &RgbaImage, split: u32) -> RgbaImage {
+    let (a, b) = align(a, b);
+    RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+        if x < split {
+            *a.get_pixel(x, y)
+        } else {
+            *b.get_pixel(x, y)
+        }
+    })
+}
+
+// `b` drawn over `a` with `opacity` in 0..=1
+pub fn onion_skin(a: &RgbaImage, b: &RgbaImage, opacity: f32) -> RgbaImage {
+    let (a, b) = align(a, b);
+    let t = opacity.clamp(0., 1.);
+    RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let mut out = [0u8; 4];
+        for i in 0..4 {
+            out[i] = (pa[i] as f32 * (1. - t) + pb[i] as f32 * t).round() as u8;
+        }
+        Rgba(out)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn canvas() -> RgbaImage {
+        RgbaImage::from_pixel(32, 24, WHITE)
+    }
+
+    fn fill(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+        for yy in y..y + h {
+            for xx in x..x + w {
+                img.put_pixel(xx, yy, color);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_images() {
+        let result = diff(&canvas(), &canvas(), &DiffOptions::default());
+        assert_eq!(result.changed_pixels, 0);
+        assert_eq!(result.ignored_pixels, 0);
+        assert!(result.regions.is_empty());
+        assert_eq!(result.changed_ratio(), 0.);
+    }
+
+    #[test]
+    fn finds_changed_regions() {
+        let mut b = canvas();
+        fill(&mut b, 2, 3, 5, 4, Rgba([0, 0, 0, 255]));
+        fill(&mut b, 20, 10, 3, 3, Rgba([255, 0, 0, 255]));
+        let result = diff(&canvas(), &b, &DiffOptions::default());
+        assert_eq!(
+            result.regions,
+            vec![
+                Region {
+                    x: 2,
+                    y: 3,
+                    width: 5,
+                    height: 4,
+                    area: 20
+                },
+                Region {
+                    x: 20,
+                    y: 10,
+                    width: 3,
+                    height: 3,
+                    area: 9
+                },
+            ]
+        );
+        assert_eq!(result.changed_pixels, 29);
+        assert_eq!(result.mask.get_pixel(2, 3)[0], 255);
+        assert_eq!(result.mask.get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn small_regions_are_ignored_and_reported() {
+        let mut b = canvas();
+        fill(&mut b, 2, 2, 4, 4, Rgba([0, 0, 0, 255]));
+        // two specks below min_region_area
+        fill(&mut b, 20, 20, 1, 1, Rgba([0, 0, 0, 255]));
+        fill(&mut b, 28, 4, 2, 1, Rgba([0, 0, 0, 255]));
+        let result = diff(&canvas(), &b, &DiffOptions::default());
+        assert_eq!(result.regions.len(), 1);
+        assert_eq!(result.changed_pixels, 16);
+        assert_eq!(result.ignored_pixels, 3);
+        // the mask and the ratio agree with the regions
+        assert_eq!(result.mask.get_pixel(20, 20)[0], 0);
+        let counted = result.mask.pixels().filter(|p| p[0] > 0).count();
+        assert_eq!(counted as u64, result.changed_pixels);
+        assert_eq!(result.changed_ratio(), 16. / (32. * 24.));
+    }
+
+    #[test]
+    fn threshold_hides_small_deltas() {
+        let mut b = canvas();
+        fill(&mut b, 0, 0, 8, 8, Rgba([245, 245, 245, 255]));
+        assert!(diff(&canvas(), &b, &DiffOptions::default())
+            .regions
+            .is_empty());
+        let strict = DiffOptions {
+            threshold: 4,
+            ..DiffOptions::default()
+        };
+        assert_eq!(diff(&canvas(), &b, &strict).changed_pixels, 64);
+    }
+
+    #[test]
+    fn different_sizes_count_the_padding() {
+        let b = RgbaImage::from_pixel(34, 24, WHITE);
+        let result = diff(&canvas(), &b, &DiffOptions::default());
+        assert_eq!(result.mask.dimensions(), (34, 24));
+        assert_eq!(result.changed_pixels, 2 * 24);
+    }
+
+    #[test]
+    fn swipe_and_onion_skin() {
+        let a = canvas();
+        let b = RgbaImage::from_pixel(32, 24, Rgba([0, 0, 0, 255]));
+        let swiped = swipe(&a, &b, 10);
+        assert_eq!(swiped.get_pixel(9, 5), &WHITE);
+        assert_eq!(swiped.get_pixel(10, 5), &Rgba([0, 0, 0, 255]));
+        let onion = onion_skin(&a, &b, 0.25);
+        assert_eq!(onion.get_pixel(0, 0), &Rgba([191, 191, 191, 255]));
+    }
+
+    #[test]
+    fn overlay_marks_changes() {
+        let mut b = canvas();
+        fill(&mut b, 10, 10, 4, 4, Rgba([0, 0, 0, 255]));
+        let result = diff(&canvas(), &b, &DiffOptions::default());
+        let out = overlay(&canvas(), &result);
+        assert_eq!(out.get_pixel(11, 11), &Rgba([255, 0, 0, 255]));
+        // the outline sits two pixels outside the region
+        assert_eq!(out.get_pixel(8, 8), &Rgba([255, 0, 255, 255]));
+        assert_eq!(out.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+    }
+}
@@ -0,0 +1,378 @@
+use std::{fmt, path::Path};
+
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{self, PngEncoder},
+    },
+    imageops::{self, FilterType},
+    ColorType, DynamicImage, ImageEncoder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::beautify;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Png,
+    Jpeg,
+    Webp,
+    // encode as all of the above and keep the smallest file
+    Smallest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportPreset {
+    pub name: String,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    // percent of the captured size, applied before the max bounds
+    pub scale: u32,
+    pub filter: Filter,
+    pub format: ExportFormat,
+    // 1-100, used by jpeg and webp
+    pub quality: u8,
+    // png only: use the slowest, best compression
+    pub png_best: bool,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Image(image::ImageError),
+    Webp(String),
+    // the output file's extension names another format than the preset
+    Extension(String, ExportFormat),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Image(err) => write!(f, "encode failed: {}", err),
+            ExportError::Webp(err) => write!(f, "webp encode failed: {}", err),
+            ExportError::Extension(ext, format) => {
+                write!(f, "a .{} file can't hold {:?} output", ext, format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<image::ImageError> for ExportError {
+    fn from(err: image::ImageError) -> Self {
+        ExportError::Image(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Encoded {
+    // never `Smallest`
+    pub format: ExportFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl Default for ExportPreset {
+    fn default() -> Self {
+        ExportPreset {
+            name: String::from("original"),
+            max_width: None,
+            max_height: None,
+            scale: 100,
+            filter: Filter::Lanczos3,
+            format: ExportFormat::Png,
+            quality: 90,
+            png_best: false,
+        }
+    }
+}
+
+impl ExportPreset {
+    pub fn builtin() -> Vec<ExportPreset> {
+        vec![
+            ExportPreset::default(),
+            ExportPreset {
+                name: String::from("web"),
+                max_width: Some(1600),
+                format: ExportFormat::Jpeg,
+                quality: 82,
+                ..ExportPreset::default()
+            },
+            ExportPreset {
+                name: String::from("half"),
+                scale: 50,
+                filter: Filter::CatmullRom,
+                format: ExportFormat::Smallest,
+                quality: 80,
+                ..ExportPreset::default()
+            },
+        ]
+    }
+
+    // size after the scale and the max bounds, aspect ratio is kept
+    pub fn target_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let mut factor = self.scale.max(1) as f64 / 100.;
+        if let Some(max) = self.max_width {
+            factor = factor.min(max as f64 / width.max(1) as f64);
+        }
+        if let Some(max) = self.max_height {
+            factor = factor.min(max as f64 / height.max(1) as f64);
+        }
+        let w = (width as f64 * factor).round().max(1.) as u32;
+        let h = (height as f64 * factor).round().max(1.) as u32;
+        (w, h)
+    }
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(ExportFormat::Png),
+            "jpg" | "jpeg" => Some(ExportFormat::Jpeg),
+            "webp" => Some(ExportFormat::Webp),
+            "smallest" | "auto" => Some(ExportFormat::Smallest),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png | ExportFormat::Smallest => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Webp => "webp",
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ExportFormat::Png | ExportFormat::Smallest => "image/png",
+            ExportFormat::Jpeg => "image/jpeg",
+            ExportFormat::Webp => "image/webp",
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(name: &str) -> Option<Filter> {
+        match name.to_lowercase().as_str() {
+            "nearest" => Some(Filter::Nearest),
+            "triangle" | "linear" => Some(Filter::Triangle),
+            "catmull_rom" | "cubic" => Some(Filter::CatmullRom),
+            "gaussian" => Some(Filter::Gaussian),
+            "lanczos3" | "lanczos" => Some(Filter::Lanczos3),
+            _ => None,
+        }
+    }
+
+    fn filter_type(&self) -> FilterType {
+        match self {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+pub fn resize(img: &DynamicImage, preset: &ExportPreset) -> DynamicImage {
+    let (w, h) = preset.target_size(img.width(), img.height());
+    if (w, h) == (img.width(), img.height()) {
+        return img.clone();
+    }
+    DynamicImage::ImageRgba8(imageops::resize(
+        &img.to_rgba8(),
+        w,
+        h,
+        preset.filter.filter_type(),
+    ))
+}
+
+// the formats `Smallest` picks from; jpeg would flatten any transparency
+// onto white
+fn smallest_candidates(img: &DynamicImage) -> Vec<ExportFormat> {
+    let opaque = img.to_rgba8().pixels().all(|p| p[3] == 0xff);
+    [ExportFormat::Png, ExportFormat::Jpeg, ExportFormat::Webp]
+        .into_iter()
+        .filter(|format| opaque || *format != ExportFormat::Jpeg)
+        .collect()
+}
+
+pub fn encode(
+    img: &DynamicImage,
+    format: ExportFormat,
+    preset: &ExportPreset,
+) -> Result<Encoded, ExportError> {
+    let mut bytes = Vec::new();
+    match format {
+        ExportFormat::Png => {
+            let rgba = img.to_rgba8();
+            let compression = if preset.png_best {
+                png::CompressionType::Best
+            } else {
+                png::CompressionType::Default
+            };
+            PngEncoder::new_with_quality(&mut bytes, compression, png::FilterType::Adaptive)
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), ColorType::Rgba8)?;
+        }
+        ExportFormat::Jpeg => {
+            let rgb = beautify::flatten(&img.to_rgba8(), [0xff, 0xff, 0xff]);
+            JpegEncoder::new_with_quality(&mut bytes, preset.quality.clamp(1, 100)).encode(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            )?;
+        }
+        ExportFormat::Webp => {
+            let rgba = img.to_rgba8();
+            let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
+            let memory = encoder.encode(preset.quality.clamp(1, 100) as f32);
+            if memory.is_empty() {
+                return Err(ExportError::Webp(String::from("empty output")));
+            }
+            bytes.extend_from_slice(&memory);
+        }
+        ExportFormat::Smallest => {
+            let mut best: Option<Encoded> = None;
+            for format in smallest_candidates(img) {
+                let encoded = encode(img, format, preset)?;
+                if best
+                    .as_ref()
+                    .is_none_or(|b| encoded.bytes.len() < b.bytes.len())
+                {
+                    best = Some(encoded);
+                }
+            }
+            return Ok(best.unwrap());
+        }
+    }
+    Ok(Encoded { format, bytes })
+}
+
+// the format to write to `path`: a known extension picks the format for
+// `Smallest` and has to agree with any other one, anything else is left to
+// the preset
+pub fn format_for_path(format: ExportFormat, path: &Path) -> Result<ExportFormat, ExportError> {
+    let ext = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext,
+        None => return Ok(format),
+    };
+    match ExportFormat::parse(ext) {
+        Some(ExportFormat::Smallest) | None => Ok(format),
+        Some(named) if format == ExportFormat::Smallest || named == format => Ok(named),
+        Some(_) => Err(ExportError::Extension(ext.to_string(), format)),
+    }
+}
+
+// resize then encode according to the preset
+pub fn export(img: &DynamicImage, preset: &ExportPreset) -> Result<Encoded, ExportError> {
+    encode(&resize(img, preset), preset.format, preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn target_size_keeps_the_aspect_ratio() {
+        let preset = ExportPreset {
+            max_width: Some(1600),
+            ..ExportPreset::default()
+        };
+        assert_eq!(preset.target_size(3200, 1000), (1600, 500));
+        assert_eq!(preset.target_size(800, 600), (800, 600));
+        let half = ExportPreset {
+            scale: 50,
+            max_height: Some(100),
+            ..ExportPreset::default()
+        };
+        assert_eq!(half.target_size(400, 300), (133, 100));
+    }
+
+    #[test]
+    fn out_extension_must_match_the_format() {
+        let png = Path::new("shot.png");
+        let jpg = Path::new("shot.JPG");
+        assert_eq!(
+            format_for_path(ExportFormat::Png, png).unwrap(),
+            ExportFormat::Png
+        );
+        assert_eq!(
+            format_for_path(ExportFormat::Jpeg, jpg).unwrap(),
+            ExportFormat::Jpeg
+        );
+        assert!(matches!(
+            format_for_path(ExportFormat::Jpeg, png),
+            Err(ExportError::Extension(_, ExportFormat::Jpeg))
+        ));
+        // the extension picks the format of `Smallest`
+        assert_eq!(
+            format_for_path(ExportFormat::Smallest, jpg).unwrap(),
+            ExportFormat::Jpeg
+        );
+        // no or unknown extensions leave the preset alone
+        assert_eq!(
+            format_for_path(ExportFormat::Webp, Path::new("shot")).unwrap(),
+            ExportFormat::Webp
+        );
+        assert_eq!(
+            format_for_path(ExportFormat::Webp, Path::new("shot.img")).unwrap(),
+            ExportFormat::Webp
+        );
+    }
+
+    #[test]
+    fn smallest_keeps_the_smallest_encoding() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+        }));
+        let preset = ExportPreset::default();
+        let smallest = encode(&img, ExportFormat::Smallest, &preset).unwrap();
+        assert_ne!(smallest.format, ExportFormat::Smallest);
+        for format in [ExportFormat::Png, ExportFormat::Jpeg, ExportFormat::Webp] {
+            let bytes = encode(&img, format, &preset).unwrap().bytes;
+            assert!(smallest.bytes.len() <= bytes.len());
+        }
+    }
+
+    #[test]
+    fn smallest_skips_jpeg_for_transparent_images() {
+        let mut pixels = RgbaImage::from_pixel(8, 8, Rgba([1, 2, 3, 255]));
+        let opaque = DynamicImage::ImageRgba8(pixels.clone());
+        assert!(smallest_candidates(&opaque).contains(&ExportFormat::Jpeg));
+        pixels.put_pixel(7, 7, Rgba([1, 2, 3, 254]));
+        let transparent = DynamicImage::ImageRgba8(pixels);
+        assert_eq!(
+            smallest_candidates(&transparent),
+            vec![ExportFormat::Png, ExportFormat::Webp]
+        );
+        let preset = ExportPreset::default();
+        let smallest = encode(&transparent, ExportFormat::Smallest, &preset).unwrap();
+        assert_ne!(smallest.format, ExportFormat::Jpeg);
+    }
+
+    #[test]
+    fn export_resizes_before_encoding() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(200, 100, Rgba([9, 9, 9, 255])));
+        let preset = ExportPreset {
+            scale: 50,
+            ..ExportPreset::default()
+        };
+        let encoded = export(&img, &preset).unwrap();
+        let decoded = image::load_from_memory(&encoded.bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 50));
+    }
+}
@@ -0,0 +1,12 @@
+use std::sync::OnceLock;
+
+use rusttype::Font;
+
+// DejaVu Sans, see assets/DejaVuSans-LICENSE.txt
+const DEJAVU_SANS: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+// parsed once, text is measured on every hover
+pub fn default_font() -> &'static Font<'static> {
+    static FONT: OnceLock<Font<'static>> = OnceLock::new();
+    FONT.get_or_init(|| Font::try_from_bytes(DEJAVU_SANS).expect("bundled font is valid"))
+}
@@ -0,0 +1,47 @@
+// golden-image checks for the tests: renders are compared against the PNGs
+// in tests/golden, run with GOLDEN_UPDATE=1 to rewrite them
+use std::path::PathBuf;
+
+use image::RgbaImage;
+
+// per-channel slack for float rounding differences between platforms
+const TOLERANCE: u8 = 2;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+pub fn check(name: &str, img: &RgbaImage) {
+    let path = path(name);
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        img.save(&path).unwrap();
+        return;
+    }
+    let golden = image::open(&path)
+        .unwrap_or_else(|err| panic!("{}: {} (run with GOLDEN_UPDATE=1)", path.display(), err))
+        .to_rgba8();
+    assert_eq!(
+        golden.dimensions(),
+        img.dimensions(),
+        "{}: size differs",
+        name
+    );
+    let off = golden
+        .pixels()
+        .zip(img.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(x, y)| x.abs_diff(y) > TOLERANCE))
+        .count();
+    if off > 0 {
+        let actual = path.with_extension("actual.png");
+        img.save(&actual).unwrap();
+        panic!(
+            "{}: {} pixels differ, wrote {}",
+            name,
+            off,
+            actual.display()
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::settings::Settings;
+
+const EN: &str = include_str!("../locales/en.toml");
+const ZH_CN: &str = include_str!("../locales/zh-CN.toml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    ZhCn,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::ZhCn];
+
+    // accepts "zh-CN", "zh_CN.UTF-8", "en_US" ...
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let tag = tag.split('.').next().unwrap_or("").to_lowercase();
+        if tag.starts_with("zh") {
+            Some(Locale::ZhCn)
+        } else if tag.starts_with("en") || tag == "c" || tag == "posix" {
+            Some(Locale::En)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_env() -> Locale {
+        for key in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+            if let Ok(value) = std::env::var(key) {
+                if value.is_empty() {
+                    continue;
+                }
+                if let Some(locale) = Locale::parse(&value) {
+                    return locale;
+                }
+            }
+        }
+        Locale::En
+    }
+
+    // the settings file wins over the environment
+    pub fn current(settings: &Settings) -> Locale {
+        settings
+            .locale
+            .as_deref()
+            .and_then(Locale::parse)
+            .unwrap_or_else(Locale::from_env)
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Locale::En => EN,
+            Locale::ZhCn => ZH_CN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    locale: Locale,
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn load(locale: Locale) -> Self {
+        let value: toml::Value = toml::from_str(locale.source())
+            .unwrap_or_else(|err| panic!("broken catalog {:?}: {}", locale, err));
+        let mut messages = HashMap::new();
+        flatten("", &value, &mut messages);
+        Catalog { locale, messages }
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(|s| s.as_str())
+    }
+
+    // falls back to the key itself so a missing entry is visible in the ui
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.get(key).unwrap_or(key)
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.messages.keys().map(|s| s.as_str()).collect();
+        keys.sort();
+        keys
+    }
+
+    // keys present in `reference` but not in this catalog
+    pub fn missing_keys(&self, reference: &Catalog) -> Vec<String> {
+        reference
+            .keys()
+            .into_iter()
+            .filter(|key| !self.messages.contains_key(*key))
+            .map(String::from)
+            .collect()
+    }
+}
+
+fn flatten(prefix: &str, value: &toml::Value, out: &mut HashMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (k, v) in table {
+                let key = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                flatten(&key, v, out);
+            }
+        }
+        toml::Value::String(s) => {
+            out.insert(prefix.to_string(), s.clone());
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+// installs the catalog used by `tr`; only the first call has an effect
+pub fn init(locale: Locale) -> &'static Catalog {
+    CATALOG.get_or_init(|| {
+        let catalog = Catalog::load(locale);
+        debug_assert!(
+            catalog.missing_keys(&Catalog::load(Locale::En)).is_empty(),
+            "catalog {:?} is missing keys",
+            locale
+        );
+        catalog
+    })
+}
+
+pub fn tr(key: &'static str) -> &'static str {
+    CATALOG
+        .get_or_init(|| Catalog::load(Locale::current(&Settings::load())))
+        .tr(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn sources(dir: &Path, out: &mut Vec<PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, out);
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                out.push(path);
+            }
+        }
+    }
+
+    // every "section.name" literal whose section is a catalog table; keys
+    // reach `tr` through helpers like `file_item`, so this scans literals
+    // rather than calls
+    fn used_keys(catalog: &Catalog) -> Vec<(PathBuf, String)> {
+        let sections: Vec<&str> = catalog
+            .keys()
+            .into_iter()
+            .filter_map(|key| key.split('.').next())
+            .collect();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut files = Vec::new();
+        sources(&root.join("src"), &mut files);
+        sources(&root.join("examples"), &mut files);
+        let mut used = Vec::new();
+        for file in files {
+            let text = std::fs::read_to_string(&file).unwrap();
+            for literal in text.split('"').skip(1) {
+                let is_key = literal.contains('.')
+                    && literal
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._".contains(c));
+                let section = literal.split('.').next().unwrap();
+                // file names such as "image.jpg" are not keys
+                let is_file = ["jpg", "png", "webp", "svg", "json"]
+                    .iter()
+                    .any(|ext| literal.ends_with(&format!(".{}", ext)));
+                if is_key && !is_file && sections.contains(&section) {
+                    used.push((file.clone(), literal.to_string()));
+                }
+            }
+        }
+        used
+    }
+
+    #[test]
+    fn catalogs_have_the_same_keys() {
+        let en = Catalog::load(Locale::En);
+        let zh = Catalog::load(Locale::ZhCn);
+        assert_eq!(zh.missing_keys(&en), Vec::<String>::new());
+        assert_eq!(en.missing_keys(&zh), Vec::<String>::new());
+    }
+
+    #[test]
+    fn used_keys_exist_in_every_catalog() {
+        let en = Catalog::load(Locale::En);
+        let used = used_keys(&en);
+        assert!(used.iter().any(|(_, key)| key == "file.menu"));
+        for locale in Locale::ALL {
+            let catalog = Catalog::load(locale);
+            let missing: Vec<_> = used
+                .iter()
+                .filter(|(_, key)| catalog.get(key).is_none())
+                .collect();
+            assert!(missing.is_empty(), "{:?} is missing {:?}", locale, missing);
+        }
+    }
+
+    #[test]
+    fn parses_locale_tags() {
+        assert_eq!(Locale::parse("zh_CN.UTF-8"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("en_US"), Some(Locale::En));
+        assert_eq!(Locale::parse("C"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr_FR"), None);
+    }
+}
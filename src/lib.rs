@@ -0,0 +1,14 @@
+pub mod beautify;
+pub mod cursor;
+pub mod diff;
+pub mod export;
+pub mod fonts;
+#[cfg(test)]
+mod golden;
+pub mod i18n;
+pub mod metadata;
+pub mod scene;
+pub mod selection;
+pub mod settings;
+pub mod upload;
+pub mod watermark;
@@ -1,23 +1,40 @@
-use std::{ops::Deref, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use core_graphics::{display::CGDisplay, image::CGImage};
 use fltk::{
     app::{self, App, Scheme},
+    button, dialog,
     draw::{self, Offscreen},
     enums::{self, *},
-    frame, menu,
+    frame, group, input, menu,
     prelude::*,
-    surface, window,
+    surface, valuator, window,
 };
 
+use fltk_demo::{
+    beautify, cursor,
+    diff::{self, DiffOptions},
+    export::{self, ExportFormat, Filter},
+    i18n,
+    metadata::{self, CaptureMetadata},
+    scene::{document, raster},
+    selection::{self, SelectionConstraint},
+    settings::Settings,
+    upload, watermark,
+};
 use image::{self, imageops, EncodableLayout};
 use tray_item::TrayItem;
 
 fn create_tray_bar() -> TrayItem {
-    let mut bar = TrayItem::new("Foo", "").unwrap();
+    let mut bar = TrayItem::new(i18n::tr("app.title"), "").unwrap();
 
     bar.add_menu_item(
-        "截屏",
+        i18n::tr("tray.capture"),
         Box::new({
             move || {
                 capture_screen_win();
@@ -26,11 +43,102 @@ fn create_tray_bar() -> TrayItem {
     )
     .unwrap();
 
-    bar.add_menu_item("Quit", Box::new(|| std::process::exit(0)))
+    bar.add_menu_item(
+        i18n::tr("tray.upload"),
+        Box::new(|| {
+            // uploads the last saved capture
+            let settings = Settings::load();
+            match last_capture(&settings) {
+                Some((path, format)) => match std::fs::read(&path) {
+                    Ok(bytes) => {
+                        // the tray runs outside any fltk app, the clipboard
+                        // and the error dialog need one
+                        let _app = app::App::default();
+                        let (tx, rx) = app::channel();
+                        let filename = format!("capture.{}", format.extension());
+                        start_upload(bytes, filename, format.mime(), &settings, tx);
+                        wait_for_upload(&rx);
+                    }
+                    Err(err) => eprintln!("{}: {}", path.display(), err),
+                },
+                None => eprintln!("nothing captured yet"),
+            }
+        }),
+    )
+    .unwrap();
+
+    bar.add_menu_item(i18n::tr("tray.quit"), Box::new(|| std::process::exit(0)))
         .unwrap();
     bar
 }
 
+fn capture_path(settings: &Settings, format: ExportFormat) -> PathBuf {
+    settings
+        .capture_dir()
+        .join(format!("image.{}", format.extension()))
+}
+
+// the most recently written image.png/jpg/webp
+fn last_capture(settings: &Settings) -> Option<(PathBuf, ExportFormat)> {
+    [ExportFormat::Png, ExportFormat::Jpeg, ExportFormat::Webp]
+        .into_iter()
+        .filter_map(|format| {
+            let path = capture_path(settings, format);
+            let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+            Some((modified, path, format))
+        })
+        .max_by_key(|(modified, _, _)| *modified)
+        .map(|(_, path, format)| (path, format))
+}
+
+// what the upload worker sends back to the ui thread
+#[derive(Debug, Clone)]
+enum Uploaded {
+    Link(String),
+    Failed(String),
+}
+
+fn start_upload(
+    bytes: Vec<u8>,
+    filename: String,
+    content_type: &str,
+    settings: &Settings,
+    tx: app::Sender<Uploaded>,
+) {
+    upload::spawn_upload(
+        bytes,
+        filename,
+        content_type.to_string(),
+        settings.upload.clone(),
+        move |result| {
+            tx.send(match result {
+                Ok(link) => Uploaded::Link(link),
+                Err(err) => Uploaded::Failed(err.to_string()),
+            })
+        },
+    );
+}
+
+// keeps the event loop going until the worker reports back, then copies the
+// link or shows the error
+fn wait_for_upload(rx: &app::Receiver<Uploaded>) {
+    while app::wait_for(0.1).is_ok() {
+        match rx.recv() {
+            Some(Uploaded::Link(link)) => {
+                app::copy(&link);
+                println!("{}", link);
+                return;
+            }
+            Some(Uploaded::Failed(err)) => {
+                eprintln!("upload failed: {}", err);
+                dialog::alert_default(&format!("{}: {}", i18n::tr("upload.failed"), err));
+                return;
+            }
+            None => (),
+        }
+    }
+}
+
 fn capture_screen_cg_image(id: u32) -> CGImage {
     let cg_display = CGDisplay::new(id);
     let cg_image = cg_display.image().unwrap();
@@ -50,7 +158,126 @@ fn bgra_to_rgba(mut bgra: Vec<u8>) -> Vec<u8> {
     bgra
 }
 
+// renders the capture frame and crops it to the selection; the watermark
+// is dated `captured_at`, when the screen was grabbed
+fn export_selection(
+    frm: &frame::Frame,
+    sel_frm: &frame::Frame,
+    settings: &Settings,
+    captured_at: u64,
+) -> image::DynamicImage {
+    let sur = surface::ImageSurface::new(frm.w(), frm.h(), false);
+    surface::ImageSurface::push_current(&sur);
+    draw::set_draw_color(enums::Color::White);
+    draw::draw_rectf(0, 0, frm.w(), frm.h());
+    sur.draw(frm, 0, 0);
+    let img = sur.image().unwrap();
+    surface::ImageSurface::pop_current();
+    let mut imgbuf: image::RgbImage = image::ImageBuffer::new(frm.w() as _, frm.h() as _);
+    imgbuf.copy_from_slice(&img.to_rgb_data());
+    let subimg = imageops::crop(
+        &mut imgbuf,
+        sel_frm.x() as u32,
+        sel_frm.y() as u32,
+        sel_frm.w() as u32,
+        sel_frm.h() as u32,
+    );
+    let mut out = image::DynamicImage::ImageRgb8(subimg.to_image());
+    if settings.watermark.enabled {
+        let mut rgba = out.to_rgba8();
+        watermark::apply(&mut rgba, &settings.watermark, captured_at);
+        out = image::DynamicImage::ImageRgba8(rgba);
+    }
+    if let Some(options) = settings.beautify_options() {
+        out = image::DynamicImage::ImageRgba8(beautify::beautify(&out.to_rgba8(), &options));
+    }
+    out
+}
+
+// moves the selection to `rect`, kept on the capture frame
+fn select(frm: &mut frame::Frame, sel_frm: &mut frame::Frame, rect: (i32, i32, i32, i32)) {
+    let (x, y, w, h) = selection::clamp_to(rect, (frm.x(), frm.y(), frm.w(), frm.h()));
+    sel_frm.resize(x, y, w, h);
+    sel_frm.redraw();
+    frm.redraw();
+}
+
+// the selection in native display pixels, dated with the grab
+fn capture_metadata(
+    sel_frm: &frame::Frame,
+    display_id: u32,
+    scale_factor: f64,
+    captured_at: u64,
+) -> CaptureMetadata {
+    let native = |v: i32| (v as f64 * scale_factor).round() as u32;
+    CaptureMetadata::new(
+        captured_at,
+        display_id,
+        (
+            native(sel_frm.x()),
+            native(sel_frm.y()),
+            native(sel_frm.w()),
+            native(sel_frm.h()),
+        ),
+        scale_factor,
+    )
+}
+
+// asks for the exact x, y, w, h of the selection
+fn selection_dialog(current: (i32, i32, i32, i32)) -> Option<(i32, i32, i32, i32)> {
+    let mut win = window::Window::default()
+        .with_size(240, 200)
+        .with_label(i18n::tr("capture.set_selection"));
+    let mut col = group::Flex::new(10, 10, 220, 180, None).column();
+    let mut inputs = Vec::new();
+    for (label, value) in ["x", "y", "w", "h"]
+        .into_iter()
+        .zip([current.0, current.1, current.2, current.3])
+    {
+        let row = group::Flex::default().row();
+        frame::Frame::default().with_label(label);
+        let mut input = input::IntInput::default();
+        input.set_value(&value.to_string());
+        inputs.push(input);
+        row.end();
+    }
+    let row = group::Flex::default().row();
+    let mut cancel = button::Button::default().with_label(i18n::tr("dialog.cancel"));
+    let mut ok = button::ReturnButton::default().with_label(i18n::tr("dialog.ok"));
+    row.end();
+    col.end();
+    win.end();
+    win.make_modal(true);
+    win.show();
+
+    let result = Rc::new(RefCell::new(None));
+    ok.set_callback({
+        let result = result.clone();
+        let mut win = win.clone();
+        move |_| {
+            let values: Vec<i32> = inputs
+                .iter()
+                .map(|input| input.value().parse().unwrap_or(0))
+                .collect();
+            if values[2] > 0 && values[3] > 0 {
+                *result.borrow_mut() = Some((values[0], values[1], values[2], values[3]));
+                win.hide();
+            }
+        }
+    });
+    cancel.set_callback({
+        let mut win = win.clone();
+        move |_| win.hide()
+    });
+    while win.shown() {
+        app::wait();
+    }
+    let result = *result.borrow();
+    result
+}
+
 fn capture_screen_win() {
+    let settings = Settings::load();
     let a = app::App::default().with_scheme(Scheme::Gtk);
     let (x, y, sw, sh) = app::screen_xywh(0);
 
@@ -67,14 +294,28 @@ fn capture_screen_win() {
 
     // let screen_image = create_screen_image();
     // let image = PngImage::from_data(screen_image.buffer());
-    let cg_image = capture_screen_cg_image(1);
+    let display_id = 1;
+    let cg_image = capture_screen_cg_image(display_id);
+    // saving can happen much later, the metadata keeps the grab time
+    let captured_at = metadata::unix_now();
 
     let cg_w = cg_image.width() as u32;
     let cg_h = cg_image.height() as u32;
+    let scale_factor = cg_w as f64 / sw as f64;
 
     let bgra = Vec::from(cg_image.data().as_bytes());
 
-    let rbga = bgra_to_rgba(bgra);
+    let mut rbga = bgra_to_rgba(bgra);
+
+    if settings.include_cursor {
+        let pointer = cursor::system_cursor_source().and_then(|src| src.cursor(scale_factor));
+        if let Some(pointer) = pointer {
+            if let Some(mut frame) = image::RgbaImage::from_raw(cg_w, cg_h, rbga.clone()) {
+                cursor::composite_cursor(&mut frame, &pointer, (0, 0));
+                rbga = frame.into_raw();
+            }
+        }
+    }
 
     let mut img =
         fltk::image::RgbImage::new(rbga.as_bytes(), cg_w as i32, cg_h as i32, ColorDepth::Rgba8)
@@ -82,43 +323,133 @@ fn capture_screen_win() {
     img.scale(sw as i32, sh as i32, true, true);
     frm.set_image(Some(img));
 
-    menu.add("关闭", EventState::None, menu::MenuFlag::Normal, {
+    menu.add(i18n::tr("capture.close"), EventState::None, menu::MenuFlag::Normal, {
         let mut win = win.clone();
         // let mut offs = offs.clone();
         move |eb| {
             win.hide();
         }
     });
-    menu.add("保存", EventState::None, menu::MenuFlag::Normal, {
+    let export_name = Rc::new(RefCell::new(
+        settings
+            .export
+            .clone()
+            .unwrap_or_else(|| String::from("original")),
+    ));
+    menu.add(i18n::tr("capture.save"), EventState::None, menu::MenuFlag::Normal, {
         let mut win = win.clone();
         let mut frm = frm.clone();
         let mut sel_frm = sel_frm.clone();
+        let settings = settings.clone();
+        let export_name = export_name.clone();
+        move |eb| {
+            let meta = capture_metadata(&sel_frm, display_id, scale_factor, captured_at);
+            let out = export_selection(&frm, &sel_frm, &settings, captured_at);
+            let preset = settings
+                .export_preset(Some(&export_name.borrow()))
+                .unwrap_or_default();
+            match export::export(&out, &preset) {
+                Ok(encoded) => {
+                    let path = capture_path(&settings, encoded.format);
+                    let written = std::fs::create_dir_all(settings.capture_dir())
+                        .and_then(|_| std::fs::write(&path, metadata::embed(encoded.bytes, &meta)));
+                    if let Err(err) = written {
+                        dialog::alert_default(&format!("{}: {}", path.display(), err));
+                    }
+                }
+                Err(err) => dialog::alert_default(&err.to_string()),
+            }
+            win.hide();
+        }
+    });
+    let (upload_tx, upload_rx) = app::channel::<Uploaded>();
+    let uploading = Rc::new(Cell::new(false));
+    menu.add(i18n::tr("capture.upload"), EventState::None, menu::MenuFlag::Normal, {
+        let mut win = win.clone();
+        let frm = frm.clone();
+        let sel_frm = sel_frm.clone();
+        let settings = settings.clone();
+        let export_name = export_name.clone();
+        let uploading = uploading.clone();
         move |eb| {
-            // println!("{:?}",(sel_frm.x(), sel_frm.y(), sel_frm.w(), sel_frm.h()));
-            let sur = surface::ImageSurface::new(frm.w(), frm.h(), false);
-            surface::ImageSurface::push_current(&sur);
-            draw::set_draw_color(enums::Color::White);
-            draw::draw_rectf(0, 0, frm.w(), frm.h());
-            sur.draw(&frm, 0, 0);
-            let img = sur.image().unwrap();
-            surface::ImageSurface::pop_current();
-            let mut imgbuf: image::RgbImage = image::ImageBuffer::new(frm.w() as _, frm.h() as _);
-            imgbuf.copy_from_slice(&img.to_rgb_data());
-            let subimg = imageops::crop(
-                &mut imgbuf,
-                sel_frm.x() as u32,
-                sel_frm.y() as u32,
-                sel_frm.w() as u32,
-                sel_frm.h() as u32,
-            );
-            subimg.to_image().save("image.jpg").unwrap();
+            let out = export_selection(&frm, &sel_frm, &settings, captured_at);
+            let preset = settings
+                .export_preset(Some(&export_name.borrow()))
+                .unwrap_or_default();
             win.hide();
+            match export::export(&out, &preset) {
+                Ok(encoded) => {
+                    let filename = format!("capture.{}", encoded.format.extension());
+                    let mime = encoded.format.mime();
+                    start_upload(encoded.bytes, filename, mime, &settings, upload_tx);
+                    uploading.set(true);
+                }
+                Err(err) => dialog::alert_default(&err.to_string()),
+            }
         }
     });
+    for preset in settings.export_presets() {
+        let path = format!("{}/{}", i18n::tr("capture.preset"), preset.name);
+        menu.add(&path, EventState::None, menu::MenuFlag::Radio, {
+            let export_name = export_name.clone();
+            let name = preset.name.clone();
+            move |eb| {
+                *export_name.borrow_mut() = name.clone();
+            }
+        });
+        if *export_name.borrow() == preset.name {
+            if let Some(mut item) = menu.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+
+    let constraint = Rc::new(RefCell::new(SelectionConstraint::Free));
+    for (i, preset) in settings.selection_presets().into_iter().enumerate() {
+        let path = format!("{}/{}", i18n::tr("capture.selection"), preset.name);
+        // the first nine presets get the digit keys as hotkeys
+        let shortcut = std::char::from_digit(i as u32 + 1, 10)
+            .filter(|_| i < 9)
+            .map(Shortcut::from_char)
+            .unwrap_or(Shortcut::None);
+        menu.add(&path, shortcut, menu::MenuFlag::Radio, {
+            let constraint = constraint.clone();
+            move |eb| {
+                *constraint.borrow_mut() = preset.constraint;
+            }
+        });
+        if i == 0 {
+            if let Some(mut item) = menu.find_item(&path) {
+                item.set();
+            }
+        }
+    }
+    menu.add(
+        &format!(
+            "{}/{}",
+            i18n::tr("capture.selection"),
+            i18n::tr("capture.set_selection")
+        ),
+        EventState::None,
+        menu::MenuFlag::Normal,
+        {
+            let mut sel_frm = sel_frm.clone();
+            let mut frm = frm.clone();
+            move |eb| {
+                let current = (sel_frm.x(), sel_frm.y(), sel_frm.w(), sel_frm.h());
+                if let Some((x, y, w, h)) = selection_dialog(current) {
+                    sel_frm.resize(x, y, w, h);
+                    sel_frm.redraw();
+                    frm.redraw();
+                }
+            }
+        },
+    );
 
     frm.handle({
         // let offs = offs.clone();
         let mut sel_frm = sel_frm.clone();
+        let constraint = constraint.clone();
         let mut tx: i32 = 0;
         let mut ty: i32 = 0;
         move |frm, e| {
@@ -130,27 +461,17 @@ fn capture_screen_win() {
                     ty = y;
                     if app::event_mouse_button() == app::MouseButton::Right {
                         menu.popup();
+                    } else if let SelectionConstraint::Fixed { .. } = *constraint.borrow() {
+                        // a fixed size needs no drag, the click places it
+                        let rect = selection::constrain((tx, ty), (x, y), *constraint.borrow());
+                        select(frm, &mut sel_frm, rect);
                     }
 
                     true
                 }
 
                 Event::Drag => {
-                    let mut tx = tx;
-                    let mut ty = ty;
-                    let ew = x - tx;
-                    let eh = y - ty;
-
-                    if ew < 0 {
-                        tx = x;
-                    }
-
-                    if eh < 0 {
-                        ty = y;
-                    }
-
-                    let ew = ew.abs() as u32;
-                    let eh = eh.abs() as u32;
+                    let rect = selection::constrain((tx, ty), (x, y), *constraint.borrow());
 
                     // offs.begin();
                     // draw::draw_image(&rbga, 0, 0, cg_w as i32, cg_h as i32, ColorDepth::Rgba8).unwrap();
@@ -160,10 +481,7 @@ fn capture_screen_win() {
                     // offs.end();
                     // frm.redraw();
 
-                    sel_frm.set_pos(tx, ty);
-                    sel_frm.set_size(ew as i32, eh as i32);
-                    sel_frm.redraw();
-                    frm.redraw();
+                    select(frm, &mut sel_frm, rect);
                     true
                 }
                 _ => false,
@@ -178,9 +496,362 @@ fn capture_screen_win() {
         }
     });
     a.run().unwrap();
+    // an upload started from the menu reports back after the window is gone
+    if uploading.get() {
+        wait_for_upload(&upload_rx);
+    }
+}
+
+fn inspect(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: fltk-demo inspect <file>...");
+        return 2;
+    }
+    let mut code = 0;
+    for file in args {
+        match metadata::read_metadata(Path::new(file)) {
+            Ok(pairs) => {
+                println!("{}", file);
+                if pairs.is_empty() {
+                    println!("  (no capture metadata)");
+                }
+                for (key, value) in pairs {
+                    println!("  {}: {}", key, value);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+fn diff_win(a: image::RgbaImage, b: image::RgbaImage, result: diff::DiffResult) {
+    let app = app::App::default();
+    let (w, h) = result.mask.dimensions();
+    let (w, h) = (w as i32, h as i32);
+    let overlay = diff::overlay(&a, &result);
+
+    let mut win = window::Window::default()
+        .with_size(w.min(1200), h.min(800) + 40)
+        .with_label(i18n::tr("diff.title"));
+    let mut frm = frame::Frame::new(0, 0, win.w(), win.h() - 40, None);
+    let mut mode = menu::Choice::new(0, win.h() - 40, 160, 40, None);
+    mode.add_choice(&format!(
+        "{}|{}|{}",
+        i18n::tr("diff.overlay"),
+        i18n::tr("diff.swipe"),
+        i18n::tr("diff.onion_skin")
+    ));
+    mode.set_value(0);
+    let mut slider = valuator::HorNiceSlider::new(160, win.h() - 40, win.w() - 160, 40, None);
+    slider.set_range(0., 1.);
+    slider.set_value(0.5);
+    win.end();
+    win.show();
+
+    // the composite is only rebuilt when the mode or the slider changes,
+    // not on every expose
+    let cache: Rc<RefCell<Option<((i32, u64), fltk::image::RgbImage)>>> =
+        Rc::new(RefCell::new(None));
+    frm.draw({
+        let mode = mode.clone();
+        let slider = slider.clone();
+        move |frm| {
+            let key = match mode.value() {
+                m @ (1 | 2) => (m, slider.value().to_bits()),
+                _ => (0, 0),
+            };
+            let mut cache = cache.borrow_mut();
+            if cache.as_ref().map(|(k, _)| *k) != Some(key) {
+                let composed = match key.0 {
+                    1 => diff::swipe(&a, &b, (slider.value() * w as f64) as u32),
+                    2 => diff::onion_skin(&a, &b, slider.value() as f32),
+                    _ => overlay.clone(),
+                };
+                let img =
+                    fltk::image::RgbImage::new(composed.as_raw(), w, h, ColorDepth::Rgba8).unwrap();
+                *cache = Some((key, img));
+            }
+            let (_, img) = cache.as_mut().unwrap();
+            img.scale(frm.w(), frm.h(), true, true);
+            img.draw(frm.x(), frm.y(), frm.w(), frm.h());
+        }
+    });
+    mode.set_callback({
+        let mut frm = frm.clone();
+        move |_| frm.redraw()
+    });
+    slider.set_callback({
+        let mut frm = frm.clone();
+        move |_| frm.redraw()
+    });
+
+    app.run().unwrap();
+}
+
+fn flag_value<T: std::str::FromStr>(iter: &mut std::slice::Iter<String>) -> Option<T> {
+    iter.next().and_then(|v| v.parse().ok())
+}
+
+fn diff_cmd(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: fltk-demo diff <before> <after> [--threshold 0-255] [--max-diff percent] [--out overlay.png] [--view]";
+    let mut files = Vec::new();
+    let mut options = DiffOptions::default();
+    let mut max_diff = 0.;
+    let mut out = None;
+    let mut view = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let ok = match arg.as_str() {
+            "--threshold" => flag_value(&mut iter)
+                .map(|v| options.threshold = v)
+                .is_some(),
+            "--max-diff" => flag_value(&mut iter).map(|v| max_diff = v).is_some(),
+            "--out" => flag_value(&mut iter).map(|v| out = Some(v)).is_some(),
+            "--view" => {
+                view = true;
+                true
+            }
+            _ => {
+                files.push(arg.clone());
+                true
+            }
+        };
+        if !ok {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let open = |path: &str| {
+        image::open(path)
+            .map(|img| img.to_rgba8())
+            .map_err(|err| eprintln!("{}: {}", path, err))
+    };
+    let (a, b) = match (open(&files[0]), open(&files[1])) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => return 2,
+    };
+
+    let result = diff::diff(&a, &b, &options);
+    let percent = result.changed_ratio() * 100.;
+    println!(
+        "{} changed pixels ({:.3}%), {} regions, {} pixels in smaller regions ignored",
+        result.changed_pixels,
+        percent,
+        result.regions.len(),
+        result.ignored_pixels
+    );
+    for region in &result.regions {
+        println!(
+            "  {}x{}+{}+{} ({} px)",
+            region.width, region.height, region.x, region.y, region.area
+        );
+    }
+
+    if let Some(out) = out {
+        if let Err(err) = diff::overlay(&a, &result).save(&out) {
+            eprintln!("{}: {}", out, err);
+            return 2;
+        }
+    }
+
+    let exceeded = percent > max_diff;
+    if view {
+        diff_win(a, b, result);
+    }
+    if exceeded {
+        1
+    } else {
+        0
+    }
+}
+
+fn export_cmd(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: fltk-demo export <input> [--preset name] [--out path] [--max-width px] [--max-height px] [--scale percent] [--filter lanczos3] [--format png|jpeg|webp|smallest] [--quality 1-100]";
+    let settings = Settings::load();
+    let mut input = None;
+    let mut out: Option<String> = None;
+    let mut preset_name: Option<String> = None;
+    let mut overrides: Vec<(String, String)> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let ok = match arg.as_str() {
+            "--preset" => flag_value(&mut iter)
+                .map(|v| preset_name = Some(v))
+                .is_some(),
+            "--out" => flag_value(&mut iter).map(|v| out = Some(v)).is_some(),
+            flag if flag.starts_with("--") => flag_value(&mut iter)
+                .map(|v| overrides.push((flag.to_string(), v)))
+                .is_some(),
+            _ => input.replace(arg.clone()).is_none(),
+        };
+        if !ok {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let mut preset = match settings.export_preset(preset_name.as_deref()) {
+        Some(preset) => preset,
+        None => {
+            eprintln!("unknown preset {:?}", preset_name.unwrap_or_default());
+            return 2;
+        }
+    };
+    for (flag, value) in overrides {
+        let ok = match flag.as_str() {
+            "--max-width" => value.parse().map(|v| preset.max_width = Some(v)).is_ok(),
+            "--max-height" => value.parse().map(|v| preset.max_height = Some(v)).is_ok(),
+            "--scale" => value.parse().map(|v| preset.scale = v).is_ok(),
+            "--quality" => value.parse().map(|v| preset.quality = v).is_ok(),
+            "--filter" => Filter::parse(&value).map(|v| preset.filter = v).is_some(),
+            "--format" => ExportFormat::parse(&value)
+                .map(|v| preset.format = v)
+                .is_some(),
+            _ => false,
+        };
+        if !ok {
+            eprintln!("bad value for {}: {}\n{}", flag, value, USAGE);
+            return 2;
+        }
+    }
+
+    // an explicit --out is written as given
+    if let Some(out) = &out {
+        match export::format_for_path(preset.format, Path::new(out)) {
+            Ok(format) => preset.format = format,
+            Err(err) => {
+                eprintln!("{}: {}", out, err);
+                return 2;
+            }
+        }
+    }
+
+    let img = match image::open(&input) {
+        Ok(img) => img,
+        Err(err) => {
+            eprintln!("{}: {}", input, err);
+            return 1;
+        }
+    };
+    let encoded = match export::export(&img, &preset) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    // without --out the extension follows the format that was actually written
+    let out = out.map(PathBuf::from).unwrap_or_else(|| {
+        let stem = Path::new(&input).file_stem().unwrap_or_default();
+        PathBuf::from(format!("{}-{}", stem.to_string_lossy(), preset.name))
+            .with_extension(encoded.format.extension())
+    });
+    if let Err(err) = std::fs::write(&out, &encoded.bytes) {
+        eprintln!("{}: {}", out.display(), err);
+        return 1;
+    }
+    println!(
+        "{} ({} bytes, {:?})",
+        out.display(),
+        encoded.bytes.len(),
+        encoded.format
+    );
+    0
+}
+
+// draws a saved demo9 drawing to a png without opening a window
+fn render_cmd(args: &[String]) -> i32 {
+    const USAGE: &str =
+        "usage: fltk-demo render <drawing.json> [--out drawing.png] [--scale factor] [--transparent]";
+    let mut input = None;
+    let mut out: Option<String> = None;
+    let mut scale = 1.;
+    let mut transparent = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let ok = match arg.as_str() {
+            "--out" => flag_value(&mut iter).map(|v| out = Some(v)).is_some(),
+            "--scale" => flag_value(&mut iter)
+                .filter(|v: &f64| *v > 0.)
+                .map(|v| scale = v)
+                .is_some(),
+            "--transparent" => {
+                transparent = true;
+                true
+            }
+            _ => input.replace(arg.clone()).is_none(),
+        };
+        if !ok {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let scene = match document::load(Path::new(&input)) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("{}: {}", input, err);
+            return 1;
+        }
+    };
+    let background = if transparent {
+        None
+    } else {
+        Some([0xff, 0xff, 0xff, 0xff])
+    };
+    let img = raster::rasterize(&scene, scale, background);
+    let out = out.unwrap_or_else(|| {
+        Path::new(&input)
+            .with_extension("png")
+            .to_string_lossy()
+            .into_owned()
+    });
+    if let Err(err) = img.save(&out) {
+        eprintln!("{}: {}", out, err);
+        return 1;
+    }
+    println!("{} ({}x{})", out, img.width(), img.height());
+    0
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("inspect") => std::process::exit(inspect(&args[2..])),
+        Some("diff") => std::process::exit(diff_cmd(&args[2..])),
+        Some("export") => std::process::exit(export_cmd(&args[2..])),
+        Some("render") => std::process::exit(render_cmd(&args[2..])),
+        _ => (),
+    }
+
+    let settings = Settings::load();
+    i18n::init(i18n::Locale::current(&settings));
+
     let mut bar = create_tray_bar();
 
     bar.inner_mut().display();
@@ -0,0 +1,589 @@
+use std::{
+    fmt, fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const TOOL_VERSION: &str = concat!("fltk-demo ", env!("CARGO_PKG_VERSION"));
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureMetadata {
+    // seconds since the unix epoch
+    pub timestamp: u64,
+    pub display_id: u32,
+    // x, y, w, h of the selection in native display pixels
+    pub region: (u32, u32, u32, u32),
+    pub scale_factor: f64,
+    pub tool_version: String,
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Png(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(err) => write!(f, "io error: {}", err),
+            MetadataError::Image(err) => write!(f, "image error: {}", err),
+            MetadataError::Png(err) => write!(f, "png error: {}", err),
+            MetadataError::Unsupported(what) => write!(f, "unsupported file: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl From<io::Error> for MetadataError {
+    fn from(err: io::Error) -> Self {
+        MetadataError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for MetadataError {
+    fn from(err: image::ImageError) -> Self {
+        MetadataError::Image(err)
+    }
+}
+
+impl CaptureMetadata {
+    // `timestamp` is when the screen was grabbed, not when it is saved
+    pub fn new(
+        timestamp: u64,
+        display_id: u32,
+        region: (u32, u32, u32, u32),
+        scale_factor: f64,
+    ) -> Self {
+        CaptureMetadata {
+            timestamp,
+            display_id,
+            region,
+            scale_factor,
+            tool_version: String::from(TOOL_VERSION),
+        }
+    }
+
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let (x, y, w, h) = self.region;
+        vec![
+            ("Capture Time", format_utc(self.timestamp)),
+            ("Display", self.display_id.to_string()),
+            ("Region", format!("{},{},{},{}", x, y, w, h)),
+            ("Scale Factor", self.scale_factor.to_string()),
+            ("Software", self.tool_version.clone()),
+        ]
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// yyyy-mm-ddThh:mm:ssZ, see http://howardhinnant.github.io/date_algorithms.html
+pub fn format_utc(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// adds the metadata to an encoded png, jpeg or webp, other formats pass through
+pub fn embed(bytes: Vec<u8>, meta: &CaptureMetadata) -> Vec<u8> {
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        embed_png_text(bytes, meta)
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        embed_jpeg_exif(bytes, meta)
+    } else if is_webp(&bytes) {
+        embed_webp_exif(bytes, meta)
+    } else {
+        bytes
+    }
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    chunk.extend_from_slice(&crc.finalize().to_be_bytes());
+    chunk
+}
+
+fn embed_png_text(bytes: Vec<u8>, meta: &CaptureMetadata) -> Vec<u8> {
+    // signature + IHDR (length, type, 13 bytes of data, crc)
+    let ihdr_end = 8 + 8 + 13 + 4;
+    if bytes.len() < ihdr_end {
+        return bytes;
+    }
+    let mut out = Vec::with_capacity(bytes.len() + 512);
+    out.extend_from_slice(&bytes[..ihdr_end]);
+    for (key, value) in meta.to_pairs() {
+        let mut data = key.as_bytes().to_vec();
+        data.push(0);
+        if value.is_ascii() {
+            data.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(&png_chunk(b"tEXt", &data));
+        } else {
+            // uncompressed, no language tag, no translated keyword
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(&png_chunk(b"iTXt", &data));
+        }
+    }
+    out.extend_from_slice(&bytes[ihdr_end..]);
+    out
+}
+
+fn embed_jpeg_exif(jpeg: Vec<u8>, meta: &CaptureMetadata) -> Vec<u8> {
+    // the APP1 segment goes after SOI and the JFIF APP0 segments, which
+    // readers expect first
+    let mut at = 2;
+    while jpeg.get(at..at + 2) == Some(&[0xff, 0xe0]) {
+        match jpeg.get(at + 2..at + 4) {
+            Some(len) => at += 2 + u16::from_be_bytes([len[0], len[1]]) as usize,
+            None => return jpeg,
+        }
+    }
+    if at > jpeg.len() {
+        return jpeg;
+    }
+    let mut exif = b"Exif\0\0".to_vec();
+    exif.extend_from_slice(&exif_tiff(meta));
+    let len = (exif.len() + 2) as u16;
+    let mut out = Vec::with_capacity(jpeg.len() + exif.len() + 4);
+    out.extend_from_slice(&jpeg[..at]);
+    out.extend_from_slice(&[0xff, 0xe1]);
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&exif);
+    out.extend_from_slice(&jpeg[at..]);
+    out
+}
+
+fn is_webp(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+}
+
+// the (fourcc, payload) chunks of a webp file
+fn webp_chunks(bytes: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let kind = [bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]];
+        let len = u32::from_le_bytes([
+            bytes[pos + 4],
+            bytes[pos + 5],
+            bytes[pos + 6],
+            bytes[pos + 7],
+        ]) as usize;
+        chunks.push((kind, bytes.get(pos + 8..pos + 8 + len)?));
+        // payloads are padded to an even size
+        pos += 8 + len + len % 2;
+    }
+    Some(chunks)
+}
+
+fn webp_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+// the VP8X header a simple (VP8 or VP8L only) file needs before it can
+// carry metadata: flags, 3 reserved bytes, canvas width - 1 and height - 1
+fn vp8x_header(kind: &[u8; 4], data: &[u8]) -> Option<Vec<u8>> {
+    let (w, h, alpha) = match kind {
+        b"VP8 " => {
+            // 3 byte frame tag, start code, then 14 bit width and height
+            if data.get(3..6)? != [0x9d, 0x01, 0x2a] {
+                return None;
+            }
+            let w = u16::from_le_bytes([*data.get(6)?, *data.get(7)?]) & 0x3fff;
+            let h = u16::from_le_bytes([*data.get(8)?, *data.get(9)?]) & 0x3fff;
+            (w as u32, h as u32, false)
+        }
+        b"VP8L" => {
+            // signature, then 14 bit width - 1, height - 1 and the alpha hint
+            if *data.first()? != 0x2f {
+                return None;
+            }
+            let bits =
+                u32::from_le_bytes([*data.get(1)?, *data.get(2)?, *data.get(3)?, *data.get(4)?]);
+            (
+                (bits & 0x3fff) + 1,
+                (bits >> 14 & 0x3fff) + 1,
+                bits >> 28 & 1 == 1,
+            )
+        }
+        _ => return None,
+    };
+    let mut header = vec![if alpha { 0x10 } else { 0 }, 0, 0, 0];
+    header.extend_from_slice(&(w - 1).to_le_bytes()[..3]);
+    header.extend_from_slice(&(h - 1).to_le_bytes()[..3]);
+    Some(header)
+}
+
+// webp keeps exif in its own chunk after the image data, flagged in VP8X
+fn embed_webp_exif(bytes: Vec<u8>, meta: &CaptureMetadata) -> Vec<u8> {
+    let chunks = match webp_chunks(&bytes) {
+        Some(chunks) if !chunks.is_empty() => chunks,
+        _ => return bytes,
+    };
+    let (kind, data) = chunks[0];
+    let header = if &kind == b"VP8X" && data.len() == 10 {
+        Some(data.to_vec())
+    } else {
+        vp8x_header(&kind, data)
+    };
+    let mut header = match header {
+        Some(header) => header,
+        None => return bytes,
+    };
+    header[0] |= 0x08;
+
+    let mut body = Vec::with_capacity(bytes.len() + 512);
+    body.extend_from_slice(b"WEBP");
+    webp_chunk(&mut body, b"VP8X", &header);
+    for (kind, data) in &chunks {
+        if kind != b"VP8X" && kind != b"EXIF" {
+            webp_chunk(&mut body, kind, data);
+        }
+    }
+    webp_chunk(&mut body, b"EXIF", &exif_tiff(meta));
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010e;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_DATE_TIME: u16 = 0x0132;
+
+// a little endian tiff with a single IFD of ascii entries. The capture
+// fields go into ImageDescription as `key: value` lines.
+fn exif_tiff(meta: &CaptureMetadata) -> Vec<u8> {
+    let description = meta
+        .to_pairs()
+        .into_iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join("\n");
+    // exif wants "YYYY:MM:DD HH:MM:SS"
+    let date = format_utc(meta.timestamp)
+        .replacen('-', ":", 2)
+        .replace('T', " ")
+        .replace('Z', "");
+    let entries = [
+        (TAG_IMAGE_DESCRIPTION, description),
+        (TAG_SOFTWARE, meta.tool_version.clone()),
+        (TAG_DATE_TIME, date),
+    ];
+
+    let ifd_offset = 8u32;
+    let ifd_len = 2 + entries.len() as u32 * 12 + 4;
+    let mut data_offset = ifd_offset + ifd_len;
+
+    let mut ifd = Vec::new();
+    let mut data = Vec::new();
+    ifd.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, value) in entries.iter() {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        ifd.extend_from_slice(&tag.to_le_bytes());
+        ifd.extend_from_slice(&2u16.to_le_bytes()); // ASCII
+        ifd.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            ifd.extend_from_slice(&bytes);
+        } else {
+            ifd.extend_from_slice(&data_offset.to_le_bytes());
+            data_offset += bytes.len() as u32;
+            data.extend_from_slice(&bytes);
+        }
+    }
+    ifd.extend_from_slice(&0u32.to_le_bytes()); // no next IFD
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"II*\0");
+    out.extend_from_slice(&ifd_offset.to_le_bytes());
+    out.extend_from_slice(&ifd);
+    out.extend_from_slice(&data);
+    out
+}
+
+// returns the key/value pairs added by `embed`
+pub fn read_metadata(path: &Path) -> Result<Vec<(String, String)>, MetadataError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(&PNG_SIGNATURE) {
+        read_png_text(&bytes)
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        Ok(read_jpeg_exif(&bytes).unwrap_or_default())
+    } else if is_webp(&bytes) {
+        Ok(read_webp_exif(&bytes).unwrap_or_default())
+    } else {
+        Err(MetadataError::Unsupported(path.display().to_string()))
+    }
+}
+
+fn read_png_text(bytes: &[u8]) -> Result<Vec<(String, String)>, MetadataError> {
+    let reader = png::Decoder::new(bytes)
+        .read_info()
+        .map_err(|err| MetadataError::Png(err.to_string()))?;
+    let info = reader.info();
+    let mut pairs = Vec::new();
+    for chunk in &info.uncompressed_latin1_text {
+        pairs.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.utf8_text {
+        let text = chunk
+            .get_text()
+            .map_err(|err| MetadataError::Png(err.to_string()))?;
+        pairs.push((chunk.keyword.clone(), text));
+    }
+    Ok(pairs)
+}
+
+fn read_jpeg_exif(bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    // walk the marker segments until APP1/Exif
+    let mut pos = 2;
+    let tiff = loop {
+        if pos + 4 > bytes.len() || bytes[pos] != 0xff {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let body = bytes.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xe1 && body.starts_with(b"Exif\0\0") {
+            break &body[6..];
+        }
+        if marker == 0xda {
+            return None;
+        }
+        pos += 2 + len;
+    };
+    read_exif_tiff(tiff)
+}
+
+fn read_webp_exif(bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    let (_, data) = webp_chunks(bytes)?
+        .into_iter()
+        .find(|(kind, _)| kind == b"EXIF")?;
+    // some writers keep the jpeg style prefix
+    read_exif_tiff(data.strip_prefix(b"Exif\0\0").unwrap_or(data))
+}
+
+fn read_exif_tiff(tiff: &[u8]) -> Option<Vec<(String, String)>> {
+    let le = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b = [
+            *tiff.get(at)?,
+            *tiff.get(at + 1)?,
+            *tiff.get(at + 2)?,
+            *tiff.get(at + 3)?,
+        ];
+        Some(if le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    let mut pairs = Vec::new();
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        let tag = u16_at(entry)?;
+        let kind = u16_at(entry + 2)?;
+        let len = u32_at(entry + 4)? as usize;
+        if kind != 2 {
+            continue;
+        }
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            u32_at(entry + 8)? as usize
+        };
+        let raw = tiff.get(start..start + len)?;
+        let text = String::from_utf8_lossy(raw)
+            .trim_end_matches('\0')
+            .to_string();
+        match tag {
+            TAG_IMAGE_DESCRIPTION => {
+                for line in text.lines() {
+                    if let Some((k, v)) = line.split_once(": ") {
+                        pairs.push((k.to_string(), v.to_string()));
+                    }
+                }
+            }
+            TAG_SOFTWARE => pairs.push((String::from("Exif Software"), text)),
+            TAG_DATE_TIME => pairs.push((String::from("Exif DateTime"), text)),
+            _ => (),
+        }
+    }
+    Some(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{self, ExportFormat, ExportPreset};
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    fn meta() -> CaptureMetadata {
+        CaptureMetadata {
+            timestamp: 1_700_000_000,
+            display_id: 2,
+            region: (10, 20, 300, 200),
+            scale_factor: 2.,
+            tool_version: String::from(TOOL_VERSION),
+        }
+    }
+
+    fn encoded(format: ExportFormat, alpha: u8) -> Vec<u8> {
+        let img = RgbaImage::from_fn(24, 16, |x, y| Rgba([x as u8 * 10, y as u8 * 15, 90, alpha]));
+        export::encode(
+            &DynamicImage::ImageRgba8(img),
+            format,
+            &ExportPreset::default(),
+        )
+        .unwrap()
+        .bytes
+    }
+
+    // writes `bytes` to a temp file and reads the metadata back
+    fn read_back(name: &str, bytes: &[u8]) -> Vec<(String, String)> {
+        let path = std::env::temp_dir().join(format!("fltk-demo-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let pairs = read_metadata(&path);
+        fs::remove_file(&path).unwrap();
+        pairs.unwrap()
+    }
+
+    fn assert_has_meta(pairs: &[(String, String)]) {
+        for (key, value) in meta().to_pairs() {
+            assert!(
+                pairs.iter().any(|(k, v)| k == key && *v == value),
+                "{} missing from {:?}",
+                key,
+                pairs
+            );
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let bytes = embed(encoded(ExportFormat::Png, 0xff), &meta());
+        assert_has_meta(&read_back("meta.png", &bytes));
+        image::load_from_memory(&bytes).unwrap();
+    }
+
+    #[test]
+    fn jpeg_exif_follows_jfif() {
+        let plain = encoded(ExportFormat::Jpeg, 0xff);
+        assert_eq!(plain[2..4], [0xff, 0xe0]);
+        let bytes = embed(plain, &meta());
+        // SOI, APP0, then our APP1
+        let app0 = 2 + 2 + u16::from_be_bytes([bytes[4], bytes[5]]) as usize;
+        assert_eq!(bytes[2..4], [0xff, 0xe0]);
+        assert_eq!(bytes[app0..app0 + 2], [0xff, 0xe1]);
+        assert_has_meta(&read_back("meta.jpg", &bytes));
+        image::load_from_memory(&bytes).unwrap();
+    }
+
+    #[test]
+    fn jpeg_without_app0() {
+        let plain = encoded(ExportFormat::Jpeg, 0xff);
+        let app0 = 2 + 2 + u16::from_be_bytes([plain[4], plain[5]]) as usize;
+        let mut bare = plain[..2].to_vec();
+        bare.extend_from_slice(&plain[app0..]);
+        let bytes = embed(bare, &meta());
+        assert_eq!(bytes[2..4], [0xff, 0xe1]);
+        assert_has_meta(&read_back("bare.jpg", &bytes));
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        // opaque input encodes as plain VP8, translucent gets VP8X + ALPH
+        for (name, alpha, first) in [
+            ("opaque.webp", 0xff, b"VP8 "),
+            ("alpha.webp", 0x80, b"VP8X"),
+        ] {
+            let plain = encoded(ExportFormat::Webp, alpha);
+            assert_eq!(&webp_chunks(&plain).unwrap()[0].0, first);
+            let bytes = embed(plain, &meta());
+            let chunks = webp_chunks(&bytes).unwrap();
+            assert_eq!(&chunks[0].0, b"VP8X");
+            assert_eq!(chunks[0].1[0] & 0x08, 0x08);
+            assert_eq!(&chunks.last().unwrap().0, b"EXIF");
+            assert_eq!(
+                u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+                bytes.len() - 8
+            );
+            assert_has_meta(&read_back(name, &bytes));
+            let decoded = webp::Decoder::new(&bytes).decode().unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (24, 16));
+        }
+    }
+
+    #[test]
+    fn vp8l_header_sizes() {
+        // 24x16 with the alpha hint set
+        let bits: u32 = 23 | 15 << 14 | 1 << 28;
+        let mut data = vec![0x2f];
+        data.extend_from_slice(&bits.to_le_bytes());
+        let header = vp8x_header(b"VP8L", &data).unwrap();
+        assert_eq!(header, [0x10, 0, 0, 0, 23, 0, 0, 15, 0, 0]);
+    }
+
+    #[test]
+    fn unknown_formats_pass_through() {
+        let bytes = b"GIF89a....".to_vec();
+        assert_eq!(embed(bytes.clone(), &meta()), bytes);
+    }
+}
@@ -0,0 +1,228 @@
+use geo::{coord, Coordinate, EuclideanDistance, Line, LineString, Point};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    begin_stroke, document::to_xy, scale_coord, Elem, ElemDoc, ElemLine, Highlight, Painter, Style,
+    GRAB_DISTANCE,
+};
+
+// head length in multiples of the stroke width
+pub const HEAD_SCALE: f64 = 4.;
+
+// segments used to flatten a curved shaft
+const CURVE_STEPS: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Head {
+    None,
+    Triangle,
+    Open,
+    Circle,
+    Bar,
+}
+
+impl Head {
+    pub const ALL: [Head; 5] = [
+        Head::None,
+        Head::Triangle,
+        Head::Open,
+        Head::Circle,
+        Head::Bar,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Head::None => "none",
+            Head::Triangle => "triangle",
+            Head::Open => "open",
+            Head::Circle => "circle",
+            Head::Bar => "bar",
+        }
+    }
+}
+
+// a line with heads. With a control point the shaft is a quadratic curve;
+// the third handle sits on the midpoint until it is dragged.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElemArrow {
+    pub line: ElemLine,
+    pub control: Option<Coordinate>,
+    pub start_head: Head,
+    pub end_head: Head,
+}
+
+impl ElemArrow {
+    pub fn new(from_coord: Coordinate, end_coord: Coordinate) -> Self {
+        ElemArrow {
+            line: ElemLine::new(from_coord, end_coord),
+            control: None,
+            start_head: Head::None,
+            end_head: Head::Triangle,
+        }
+    }
+
+    pub fn with_heads(mut self, start_head: Head, end_head: Head) -> Self {
+        self.start_head = start_head;
+        self.end_head = end_head;
+        self
+    }
+
+    fn control_or_mid(&self) -> Coordinate {
+        let (a, b) = (self.line.from_coord, self.line.end_coord);
+        self.control
+            .unwrap_or(coord! {x: (a.x + b.x) / 2., y: (a.y + b.y) / 2.})
+    }
+
+    // the shaft as a point list, two points when straight
+    pub fn shaft(&self) -> Vec<Coordinate> {
+        let (a, b) = (self.line.from_coord, self.line.end_coord);
+        match self.control {
+            None => vec![a, b],
+            Some(c) => (0..=CURVE_STEPS)
+                .map(|i| {
+                    let t = i as f64 / CURVE_STEPS as f64;
+                    let u = 1. - t;
+                    coord! {
+                        x: u * u * a.x + 2. * u * t * c.x + t * t * b.x,
+                        y: u * u * a.y + 2. * u * t * c.y + t * t * b.y,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn draw_head(
+    painter: &mut dyn Painter,
+    head: Head,
+    tip: Coordinate,
+    toward: Coordinate,
+    width: f64,
+) {
+    let (dx, dy) = (tip.x - toward.x, tip.y - toward.y);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0. {
+        return;
+    }
+    // u points along the shaft into the tip, n across it
+    let (ux, uy) = (dx / len, dy / len);
+    let (nx, ny) = (-uy, ux);
+    let size = width * HEAD_SCALE;
+    let at = |along: f64, across: f64| {
+        coord! {
+            x: tip.x + ux * along + nx * across,
+            y: tip.y + uy * along + ny * across,
+        }
+    };
+    match head {
+        Head::None => (),
+        Head::Triangle => painter.fill_polygon(&[tip, at(-size, size / 2.), at(-size, -size / 2.)]),
+        Head::Open => {
+            painter.line(at(-size, size / 2.), tip);
+            painter.line(tip, at(-size, -size / 2.));
+        }
+        Head::Circle => {
+            let r = size / 3.;
+            let circle: Vec<Coordinate> = (0..24)
+                .map(|i| {
+                    let a = i as f64 / 24. * std::f64::consts::TAU;
+                    coord! {x: tip.x + r * a.cos(), y: tip.y + r * a.sin()}
+                })
+                .collect();
+            painter.fill_polygon(&circle);
+        }
+        Head::Bar => painter.line(at(0., size / 2.), at(0., -size / 2.)),
+    }
+}
+
+impl Elem for ElemArrow {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.line.style, highlight);
+        let (a, b) = (self.line.from_coord, self.line.end_coord);
+        match self.control {
+            None => painter.line(a, b),
+            Some(c) => painter.quad(a, c, b),
+        }
+        // heads follow the tangent at each end and are always solid
+        let style = self.line.style.highlighted(highlight);
+        painter.set_stroke(style.stroke_color(), style.width);
+        let c = self.control.unwrap_or(a);
+        draw_head(painter, self.end_head, b, c, style.width);
+        let c = self.control.unwrap_or(b);
+        draw_head(painter, self.start_head, a, c, style.width);
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        vec![
+            self.line.from_coord,
+            self.line.end_coord,
+            self.control_or_mid(),
+        ]
+    }
+
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.line.creating(from_coord, end_coord);
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.line.edit_moving(from_coord, end_coord);
+        if let Some(c) = &mut self.control {
+            c.x += end_coord.x - from_coord.x;
+            c.y += end_coord.y - from_coord.y;
+        }
+    }
+
+    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        match drag_vertex {
+            0 | 1 => self.line.edit_resizing(from_coord, end_coord, drag_vertex),
+            2 => self.control = Some(end_coord),
+            _ => (),
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        self.line.scale(origin, sx, sy);
+        self.control = self.control.map(|c| scale_coord(c, origin, sx, sy));
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        let shaft = self.shaft();
+        let near_shaft = if shaft.len() == 2 {
+            mouse_point.euclidean_distance(&Line::new(shaft[0], shaft[1])) < GRAB_DISTANCE
+        } else {
+            mouse_point.euclidean_distance(&LineString::from(shaft)) < GRAB_DISTANCE
+        };
+        near_shaft
+            || self
+                .get_vertex()
+                .iter()
+                .any(|v| Point::from(*v).euclidean_distance(&mouse_point) < GRAB_DISTANCE)
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.line.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.line.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(*self)
+    }
+
+    fn kind(&self) -> &'static str {
+        "arrow"
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Arrow {
+            from: to_xy(self.line.from_coord),
+            to: to_xy(self.line.end_coord),
+            control: self.control.map(to_xy),
+            start_head: self.start_head,
+            end_head: self.end_head,
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::mem::size_of;
+
+use geo::{coord, Contains, Coordinate, EuclideanDistance, LineString, Point, Polygon};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    begin_stroke, document::from_xy, document::to_xy, scale_coord, Elem, ElemDoc, Highlight,
+    Painter, Style, DARK_RED, GRAB_DISTANCE,
+};
+
+// segments used to flatten one cubic for hit testing and rasterizing
+pub const CURVE_STEPS: usize = 24;
+
+pub fn cubic_point(
+    p0: Coordinate,
+    p1: Coordinate,
+    p2: Coordinate,
+    p3: Coordinate,
+    t: f64,
+) -> Coordinate {
+    let u = 1. - t;
+    let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+    coord! {
+        x: a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+        y: a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+    }
+}
+
+// `points` as given to `Painter::bezier`: a start point, then control,
+// control and end point for every segment
+pub fn flatten(points: &[Coordinate], steps: usize) -> Vec<Coordinate> {
+    let mut out: Vec<Coordinate> = points.iter().take(1).cloned().collect();
+    for seg in points.windows(4).step_by(3) {
+        for i in 1..=steps {
+            let t = i as f64 / steps as f64;
+            out.push(cubic_point(seg[0], seg[1], seg[2], seg[3], t));
+        }
+    }
+    out
+}
+
+// an anchor with its two control points; a corner has both on the anchor
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Node {
+    pub anchor: Coordinate,
+    pub in_ctrl: Coordinate,
+    pub out_ctrl: Coordinate,
+    // dragging one control turns the other with it
+    pub smooth: bool,
+}
+
+impl Node {
+    pub fn corner(anchor: Coordinate) -> Self {
+        Node {
+            anchor,
+            in_ctrl: anchor,
+            out_ctrl: anchor,
+            smooth: false,
+        }
+    }
+
+    fn translate(&mut self, dx: f64, dy: f64) {
+        for p in [&mut self.anchor, &mut self.in_ctrl, &mut self.out_ctrl] {
+            p.x += dx;
+            p.y += dy;
+        }
+    }
+
+    // the out control on `to`, the in control mirrored
+    fn pull(&mut self, to: Coordinate) {
+        self.out_ctrl = to;
+        self.in_ctrl = coord! {
+            x: 2. * self.anchor.x - to.x,
+            y: 2. * self.anchor.y - to.y,
+        };
+        self.smooth = to != self.anchor;
+    }
+}
+
+// the same as `Node` with [x, y] points, for documents
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDoc {
+    pub anchor: [f64; 2],
+    pub in_ctrl: [f64; 2],
+    pub out_ctrl: [f64; 2],
+    #[serde(default)]
+    pub smooth: bool,
+}
+
+impl From<Node> for NodeDoc {
+    fn from(node: Node) -> Self {
+        NodeDoc {
+            anchor: to_xy(node.anchor),
+            in_ctrl: to_xy(node.in_ctrl),
+            out_ctrl: to_xy(node.out_ctrl),
+            smooth: node.smooth,
+        }
+    }
+}
+
+impl From<NodeDoc> for Node {
+    fn from(doc: NodeDoc) -> Self {
+        Node {
+            anchor: from_xy(doc.anchor),
+            in_ctrl: from_xy(doc.in_ctrl),
+            out_ctrl: from_xy(doc.out_ctrl),
+            smooth: doc.smooth,
+        }
+    }
+}
+
+// A path of cubic segments, built like a pen tool: a click places a corner,
+// a drag pulls out smooth handles. Clicking the first anchor closes it.
+// Handles are the anchors first, then the in and out control of each node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElemBezier {
+    pub nodes: Vec<Node>,
+    pub closed: bool,
+    pub style: Style,
+    preview: Option<Coordinate>,
+    placing: bool,
+    // the node the creating drag pulls handles from
+    active: usize,
+    constrained: bool,
+}
+
+impl ElemBezier {
+    pub fn new(nodes: Vec<Node>, closed: bool) -> Self {
+        ElemBezier {
+            nodes,
+            closed,
+            style: Style::default(),
+            preview: None,
+            placing: false,
+            active: 0,
+            constrained: false,
+        }
+    }
+
+    // an empty path that is built by clicks
+    pub fn placed() -> Self {
+        ElemBezier {
+            placing: true,
+            ..ElemBezier::new(Vec::new(), false)
+        }
+    }
+
+    // start point, then control, control, end for each segment
+    pub fn points(&self) -> Vec<Coordinate> {
+        let mut points = Vec::new();
+        let first = match self.nodes.first() {
+            Some(first) => first,
+            None => return points,
+        };
+        points.push(first.anchor);
+        for w in self.nodes.windows(2) {
+            points.extend([w[0].out_ctrl, w[1].in_ctrl, w[1].anchor]);
+        }
+        if self.closed && self.nodes.len() > 1 {
+            let last = self.nodes[self.nodes.len() - 1];
+            points.extend([last.out_ctrl, first.in_ctrl, first.anchor]);
+        }
+        points
+    }
+
+    pub fn outline(&self) -> Vec<Coordinate> {
+        flatten(&self.points(), CURVE_STEPS)
+    }
+}
+
+impl Elem for ElemBezier {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.style, highlight);
+        if self.placing {
+            let mut points = self.points();
+            if let (Some(last), Some(p)) = (self.nodes.last(), self.preview) {
+                points.extend([last.out_ctrl, p, p]);
+            }
+            painter.bezier(&points, false);
+        } else {
+            painter.bezier(&self.points(), self.closed);
+        }
+        // the arms of the control points
+        if highlight == Highlight::Selected || self.placing {
+            painter.set_stroke(DARK_RED, 1.);
+            for node in &self.nodes {
+                for ctrl in [node.in_ctrl, node.out_ctrl] {
+                    if ctrl != node.anchor {
+                        painter.line(node.anchor, ctrl);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        let mut vertex: Vec<Coordinate> = self.nodes.iter().map(|n| n.anchor).collect();
+        if !self.placing {
+            vertex.extend(self.nodes.iter().flat_map(|n| [n.in_ctrl, n.out_ctrl]));
+        }
+        vertex
+    }
+
+    // only drags while placing get here: they pull handles out of the
+    // node just placed
+    fn creating(&mut self, _from_coord: Coordinate, end_coord: Coordinate) {
+        if let Some(node) = self.nodes.get_mut(self.active) {
+            node.pull(end_coord);
+        }
+    }
+
+    fn placed_by_clicks(&self) -> bool {
+        self.placing
+    }
+
+    fn place_vertex(&mut self, coord: Coordinate) {
+        let closes = self.nodes.len() >= 2
+            && Point::from(self.nodes[0].anchor).euclidean_distance(&Point::from(coord))
+                < GRAB_DISTANCE;
+        if closes {
+            // the scene finishes the path on release
+            self.closed = true;
+            self.placing = false;
+            self.active = 0;
+        } else {
+            self.nodes.push(Node::corner(coord));
+            self.active = self.nodes.len() - 1;
+        }
+    }
+
+    fn preview_vertex(&mut self, coord: Option<Coordinate>) {
+        self.preview = coord;
+    }
+
+    fn finish_creating(&mut self) {
+        self.placing = false;
+        self.preview = None;
+        // a double click lands twice on the same spot
+        self.nodes.dedup_by(|b, a| a.anchor == b.anchor);
+        let n = self.nodes.len();
+        if self.closed && n > 1 && self.nodes[0].anchor == self.nodes[n - 1].anchor {
+            self.nodes.pop();
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.nodes.len() >= 2
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let dx = end_coord.x - from_coord.x;
+        let dy = end_coord.y - from_coord.y;
+        for node in &mut self.nodes {
+            node.translate(dx, dy);
+        }
+    }
+
+    // An anchor moves with its controls, or pulls out smooth handles with
+    // Shift. A control of a smooth node turns the other one too, Shift
+    // breaks them apart.
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        let n = self.nodes.len();
+        let index = drag_vertex as usize;
+        if drag_vertex < 0 || index >= n * 3 {
+            return;
+        }
+        if index < n {
+            let node = &mut self.nodes[index];
+            if self.constrained {
+                node.pull(end_coord);
+            } else {
+                node.translate(end_coord.x - node.anchor.x, end_coord.y - node.anchor.y);
+            }
+            return;
+        }
+        let node = &mut self.nodes[(index - n) / 2];
+        let is_out = (index - n) % 2 == 1;
+        if self.constrained {
+            node.smooth = false;
+        }
+        let (moved, other) = if is_out {
+            (&mut node.out_ctrl, &mut node.in_ctrl)
+        } else {
+            (&mut node.in_ctrl, &mut node.out_ctrl)
+        };
+        *moved = end_coord;
+        if node.smooth {
+            let a = node.anchor;
+            let len = Point::from(a).euclidean_distance(&Point::from(*other));
+            let (dx, dy) = (a.x - end_coord.x, a.y - end_coord.y);
+            let d = (dx * dx + dy * dy).sqrt();
+            if d > 0. {
+                *other = coord! {x: a.x + dx / d * len, y: a.y + dy / d * len};
+            }
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        for node in &mut self.nodes {
+            for p in [&mut node.anchor, &mut node.in_ctrl, &mut node.out_ctrl] {
+                *p = scale_coord(*p, origin, sx, sy);
+            }
+        }
+    }
+
+    fn set_constrained(&mut self, constrained: bool) {
+        self.constrained = constrained;
+    }
+
+    fn remove_vertex(&mut self, index: i32) -> bool {
+        let index = index as usize;
+        if index >= self.nodes.len() || self.nodes.len() <= 2 {
+            return false;
+        }
+        self.nodes.remove(index);
+        true
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let near_vertex = self
+            .get_vertex()
+            .iter()
+            .any(|v| Point::from(*v).euclidean_distance(&mouse_point) < GRAB_DISTANCE);
+        let outline = LineString::from(self.outline());
+        if self.closed && self.nodes.len() > 1 {
+            let polygon = Polygon::new(outline, vec![]);
+            near_vertex
+                || polygon.contains(&mouse_point)
+                || mouse_point.euclidean_distance(polygon.exterior()) < GRAB_DISTANCE
+        } else {
+            near_vertex || mouse_point.euclidean_distance(&outline) < GRAB_DISTANCE
+        }
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(self.clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        "bezier"
+    }
+
+    fn heap_size(&self) -> usize {
+        self.nodes.len() * size_of::<Node>()
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Bezier {
+            nodes: self.nodes.iter().map(|n| NodeDoc::from(*n)).collect(),
+            closed: self.closed,
+        }
+    }
+}
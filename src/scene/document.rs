@@ -0,0 +1,635 @@
+// Versioned JSON documents for a `Scene`.
+//
+// Files are read as plain JSON first so older versions can be migrated
+// step by step before they are deserialized into the current types.
+
+use std::{fmt, fs, io, path::Path};
+
+use geo::{coord, Coordinate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    Elem, ElemArrow, ElemBezier, ElemEllipse, ElemFreehand, ElemImage, ElemLine, ElemPolyline,
+    ElemRect, ElemText, Head, NodeDoc, Scene, Style, TextStyle,
+};
+
+pub const CURRENT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    // written by a newer build
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Io(err) => write!(f, "io error: {}", err),
+            DocumentError::Json(err) => write!(f, "invalid json: {}", err),
+            DocumentError::UnsupportedVersion(v) => write!(
+                f,
+                "document version {} is newer than supported version {}",
+                v, CURRENT_VERSION
+            ),
+            DocumentError::Invalid(what) => write!(f, "invalid document: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<io::Error> for DocumentError {
+    fn from(err: io::Error) -> Self {
+        DocumentError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(err: serde_json::Error) -> Self {
+        DocumentError::Json(err)
+    }
+}
+
+// one variant per element kind, points are [x, y]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ElemDoc {
+    Line {
+        from: [f64; 2],
+        to: [f64; 2],
+    },
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    Ellipse {
+        cx: f64,
+        cy: f64,
+        rx: f64,
+        ry: f64,
+    },
+    Arrow {
+        from: [f64; 2],
+        to: [f64; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        control: Option<[f64; 2]>,
+        start_head: Head,
+        end_head: Head,
+    },
+    Freehand {
+        points: Vec<[f64; 2]>,
+        #[serde(default)]
+        smooth: bool,
+    },
+    Polyline {
+        points: Vec<[f64; 2]>,
+    },
+    Polygon {
+        points: Vec<[f64; 2]>,
+    },
+    Text {
+        origin: [f64; 2],
+        text: String,
+        text_style: TextStyle,
+    },
+    Bezier {
+        nodes: Vec<NodeDoc>,
+        #[serde(default)]
+        closed: bool,
+    },
+    Image {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        #[serde(default = "full_opacity")]
+        opacity: f64,
+        // a data URI or a file path
+        src: String,
+    },
+}
+
+fn full_opacity() -> f64 {
+    1.
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElemEntry {
+    pub id: u64,
+    #[serde(flatten)]
+    pub elem: ElemDoc,
+    // absent for elements without a `Style`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanvasDoc {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub canvas: CanvasDoc,
+    pub elements: Vec<ElemEntry>,
+}
+
+pub fn to_xy(c: Coordinate) -> [f64; 2] {
+    [c.x, c.y]
+}
+
+pub fn from_xy(p: [f64; 2]) -> Coordinate {
+    coord! {x: p[0], y: p[1]}
+}
+
+impl ElemDoc {
+    pub fn into_elem(self) -> Box<dyn Elem> {
+        match self {
+            ElemDoc::Line { from, to } => Box::new(ElemLine::new(from_xy(from), from_xy(to))),
+            ElemDoc::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => Box::new(ElemRect::new(coord! {x: x, y: y}, width, height)),
+            ElemDoc::Ellipse { cx, cy, rx, ry } => {
+                Box::new(ElemEllipse::new(coord! {x: cx, y: cy}, rx, ry))
+            }
+            ElemDoc::Arrow {
+                from,
+                to,
+                control,
+                start_head,
+                end_head,
+            } => {
+                let mut arrow =
+                    ElemArrow::new(from_xy(from), from_xy(to)).with_heads(start_head, end_head);
+                arrow.control = control.map(from_xy);
+                Box::new(arrow)
+            }
+            ElemDoc::Freehand { points, smooth } => Box::new(ElemFreehand::new(
+                points.into_iter().map(from_xy).collect(),
+                smooth,
+            )),
+            ElemDoc::Polyline { points } => Box::new(ElemPolyline::new(
+                points.into_iter().map(from_xy).collect(),
+                false,
+            )),
+            ElemDoc::Polygon { points } => Box::new(ElemPolyline::new(
+                points.into_iter().map(from_xy).collect(),
+                true,
+            )),
+            ElemDoc::Text {
+                origin,
+                text,
+                text_style,
+            } => Box::new(ElemText::new(from_xy(origin), &text, text_style)),
+            ElemDoc::Bezier { nodes, closed } => Box::new(ElemBezier::new(
+                nodes.into_iter().map(Into::into).collect(),
+                closed,
+            )),
+            ElemDoc::Image {
+                x,
+                y,
+                width,
+                height,
+                opacity,
+                src,
+            } => Box::new(ElemImage::from_src(
+                ElemRect::new(coord! {x: x, y: y}, width, height),
+                opacity,
+                src,
+            )),
+        }
+    }
+}
+
+impl Document {
+    pub fn from_scene(scene: &Scene) -> Self {
+        let (width, height) = scene.canvas_size();
+        Document {
+            version: CURRENT_VERSION,
+            canvas: CanvasDoc { width, height },
+            elements: scene
+                .iter()
+                .map(|(id, elem)| ElemEntry {
+                    id,
+                    elem: elem.to_doc(),
+                    style: elem.style().copied(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn into_scene(self) -> Scene {
+        let mut scene = Scene::new();
+        scene.set_canvas_size(self.canvas.width, self.canvas.height);
+        for entry in self.elements {
+            let index = scene.len();
+            let mut elem = entry.elem.into_elem();
+            if let (Some(style), Some(elem_style)) = (entry.style, elem.style_mut()) {
+                *elem_style = style;
+            }
+            scene.insert(index, entry.id, elem);
+        }
+        scene
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("document is always serializable")
+    }
+
+    pub fn from_json(text: &str) -> Result<Document, DocumentError> {
+        let value: Value = serde_json::from_str(text)?;
+        let value = migrate(value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+// the first version ever written; files without a version are not ours
+const FIRST_VERSION: u32 = 1;
+
+// upgrades a document from version `FIRST_VERSION + i` to the next one
+type Migration = fn(Value) -> Result<Value, DocumentError>;
+
+// one entry is added here whenever `CURRENT_VERSION` is bumped
+const MIGRATIONS: &[Migration] = &[];
+
+pub fn document_version(doc: &Value) -> Option<u32> {
+    doc.get("version")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+}
+
+pub fn migrate(doc: Value) -> Result<Value, DocumentError> {
+    migrate_with(doc, MIGRATIONS)
+}
+
+// runs `migrations` in turn until the document is at the version after the
+// last of them
+fn migrate_with(mut doc: Value, migrations: &[Migration]) -> Result<Value, DocumentError> {
+    let current = FIRST_VERSION + migrations.len() as u32;
+    loop {
+        let version = document_version(&doc)
+            .ok_or_else(|| DocumentError::Invalid(String::from("no version")))?;
+        if version == current {
+            return Ok(doc);
+        }
+        if version > current {
+            return Err(DocumentError::UnsupportedVersion(version));
+        }
+        let migration = version
+            .checked_sub(FIRST_VERSION)
+            .and_then(|i| migrations.get(i as usize))
+            .ok_or_else(|| DocumentError::Invalid(format!("unknown version {}", version)))?;
+        doc = migration(doc)?;
+        if document_version(&doc).is_none_or(|v| v <= version) {
+            return Err(DocumentError::Invalid(format!(
+                "migration from version {} did not advance",
+                version
+            )));
+        }
+    }
+}
+
+pub fn save(scene: &Scene, path: &Path) -> Result<(), DocumentError> {
+    fs::write(path, Document::from_scene(scene).to_json())?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Scene, DocumentError> {
+    let text = fs::read_to_string(path)?;
+    Ok(Document::from_json(&text)?.into_scene())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use serde_json::json;
+
+    use super::*;
+    use crate::scene::{Align, Cap, Color, Dash, FontFamily, Join, Node};
+
+    fn round_trip(scene: &Scene) -> Scene {
+        let json = Document::from_scene(scene).to_json();
+        Document::from_json(&json).unwrap().into_scene()
+    }
+
+    #[test]
+    fn every_element_kind_round_trips() {
+        let mut scene = Scene::new();
+        scene.set_canvas_size(640, 480);
+        let styled = Style {
+            dash: Dash::DashDot,
+            fill: Some([0, 0x80, 0xff, 0x40]),
+            opacity: 0.5,
+            ..Style::solid([0x10, 0x20, 0x30, 0xff], 2.5)
+        };
+        let mut line = ElemLine::new(coord! {x: 1., y: 2.}, coord! {x: 30.5, y: 40.});
+        line.style = styled;
+        let mut arrow = ElemArrow::new(coord! {x: 5., y: 5.}, coord! {x: 90., y: 20.})
+            .with_heads(Head::Circle, Head::Open);
+        arrow.control = Some(coord! {x: 40., y: 60.});
+        let mut bezier_node = Node::corner(coord! {x: 100., y: 100.});
+        bezier_node.out_ctrl = coord! {x: 120., y: 80.};
+        let elems: Vec<Box<dyn Elem>> = vec![
+            Box::new(line),
+            Box::new(ElemRect::new(coord! {x: 10., y: 20.}, 30., 40.)),
+            Box::new(ElemEllipse::new(coord! {x: 50., y: 50.}, 20., 10.)),
+            Box::new(arrow),
+            Box::new(ElemFreehand::new(
+                vec![
+                    coord! {x: 0., y: 0.},
+                    coord! {x: 3., y: 4.},
+                    coord! {x: 8., y: 1.},
+                ],
+                true,
+            )),
+            Box::new(ElemPolyline::new(
+                vec![coord! {x: 0., y: 0.}, coord! {x: 10., y: 0.}],
+                false,
+            )),
+            Box::new(ElemPolyline::new(
+                vec![
+                    coord! {x: 0., y: 0.},
+                    coord! {x: 10., y: 0.},
+                    coord! {x: 5., y: 8.},
+                ],
+                true,
+            )),
+            Box::new(ElemText::new(
+                coord! {x: 12., y: 14.},
+                "two\nlines",
+                TextStyle {
+                    size: 18.,
+                    ..TextStyle::default()
+                },
+            )),
+            Box::new(ElemBezier::new(
+                vec![bezier_node, Node::corner(coord! {x: 160., y: 120.})],
+                true,
+            )),
+            Box::new(ElemImage::from_src(
+                ElemRect::new(coord! {x: 200., y: 10.}, 64., 48.),
+                0.75,
+                String::from("pictures/missing.png"),
+            )),
+        ];
+        for elem in elems {
+            scene.push(elem);
+        }
+
+        let before = Document::from_scene(&scene);
+        let kinds = before
+            .elements
+            .iter()
+            .map(|e| serde_json::to_value(&e.elem).unwrap()["type"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "line", "rect", "ellipse", "arrow", "freehand", "polyline", "polygon", "text",
+                "bezier", "image"
+            ]
+        );
+        // `kind` names the saved type without building the document
+        let named = scene.iter().map(|(_, e)| e.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, named);
+        assert_eq!(Document::from_scene(&round_trip(&scene)), before);
+    }
+
+    // characters that need escaping or more than one byte
+    const TEXT_CHARS: &[&str] = &[
+        "a", "Z", "0", " ", "\n", "\"", "'", "\\", "<", "&", "\t", "é", "中", "😀", "\u{7f}",
+    ];
+
+    fn random_point(rng: &mut StdRng) -> [f64; 2] {
+        [
+            rng.gen_range(-5000.0..5000.0),
+            rng.gen_range(-5000.0..5000.0),
+        ]
+    }
+
+    // lists are empty now and then, the loader must not mind
+    fn random_points(rng: &mut StdRng) -> Vec<[f64; 2]> {
+        let n = [0, 1, 2, rng.gen_range(3..40)][rng.gen_range(0..4)];
+        (0..n).map(|_| random_point(rng)).collect()
+    }
+
+    fn random_color(rng: &mut StdRng) -> Color {
+        rng.gen()
+    }
+
+    fn random_text(rng: &mut StdRng) -> String {
+        let n = rng.gen_range(0..24);
+        (0..n).map(|_| *TEXT_CHARS.choose(rng).unwrap()).collect()
+    }
+
+    fn random_style(rng: &mut StdRng) -> Style {
+        Style {
+            stroke: random_color(rng),
+            width: rng.gen_range(0.0..40.0),
+            dash: *Dash::ALL.choose(rng).unwrap(),
+            cap: *Cap::ALL.choose(rng).unwrap(),
+            join: *Join::ALL.choose(rng).unwrap(),
+            fill: rng.gen::<bool>().then(|| random_color(rng)),
+            opacity: rng.gen_range(0.0..=1.0),
+        }
+    }
+
+    fn random_elem(rng: &mut StdRng) -> ElemDoc {
+        match rng.gen_range(0..10) {
+            0 => ElemDoc::Line {
+                from: random_point(rng),
+                to: random_point(rng),
+            },
+            1 => ElemDoc::Rect {
+                x: rng.gen_range(-5000.0..5000.0),
+                y: rng.gen_range(-5000.0..5000.0),
+                width: rng.gen_range(0.0..5000.0),
+                height: rng.gen_range(0.0..5000.0),
+            },
+            // the ellipse is kept as its box, so the center and radii are
+            // quarters that survive the trip through it exactly
+            2 => ElemDoc::Ellipse {
+                cx: rng.gen_range(-20000..20000) as f64 / 4.,
+                cy: rng.gen_range(-20000..20000) as f64 / 4.,
+                rx: rng.gen_range(0..20000) as f64 / 4.,
+                ry: rng.gen_range(0..20000) as f64 / 4.,
+            },
+            3 => ElemDoc::Arrow {
+                from: random_point(rng),
+                to: random_point(rng),
+                control: rng.gen::<bool>().then(|| random_point(rng)),
+                start_head: *Head::ALL.choose(rng).unwrap(),
+                end_head: *Head::ALL.choose(rng).unwrap(),
+            },
+            4 => ElemDoc::Freehand {
+                points: random_points(rng),
+                smooth: rng.gen(),
+            },
+            5 => ElemDoc::Polyline {
+                points: random_points(rng),
+            },
+            6 => ElemDoc::Polygon {
+                points: random_points(rng),
+            },
+            7 => ElemDoc::Text {
+                origin: random_point(rng),
+                text: random_text(rng),
+                text_style: TextStyle {
+                    font: *FontFamily::ALL.choose(rng).unwrap(),
+                    size: rng.gen_range(1.0..200.0),
+                    color: random_color(rng),
+                    align: *Align::ALL.choose(rng).unwrap(),
+                },
+            },
+            8 => ElemDoc::Bezier {
+                nodes: random_points(rng)
+                    .into_iter()
+                    .map(|anchor| NodeDoc {
+                        anchor,
+                        in_ctrl: random_point(rng),
+                        out_ctrl: random_point(rng),
+                        smooth: rng.gen(),
+                    })
+                    .collect(),
+                closed: rng.gen(),
+            },
+            _ => ElemDoc::Image {
+                x: rng.gen_range(-5000.0..5000.0),
+                y: rng.gen_range(-5000.0..5000.0),
+                width: rng.gen_range(0.0..5000.0),
+                height: rng.gen_range(0.0..5000.0),
+                opacity: rng.gen_range(0.0..=1.0),
+                // never read successfully, which has to load all the same
+                src: format!("missing/{}.png", random_text(rng)),
+            },
+        }
+    }
+
+    fn random_document(rng: &mut StdRng) -> Document {
+        let mut ids = (1..10_000u64).collect::<Vec<_>>();
+        ids.shuffle(rng);
+        let n = rng.gen_range(0..30);
+        let elements = ids[..n]
+            .iter()
+            .map(|&id| {
+                let elem = random_elem(rng);
+                // only shapes with a stroke keep a style
+                let styled = elem.clone().into_elem().style().is_some();
+                ElemEntry {
+                    id,
+                    elem,
+                    style: styled.then(|| random_style(rng)),
+                }
+            })
+            .collect();
+        Document {
+            version: CURRENT_VERSION,
+            canvas: CanvasDoc {
+                width: rng.gen_range(1..8000),
+                height: rng.gen_range(1..8000),
+            },
+            elements,
+        }
+    }
+
+    #[test]
+    fn random_documents_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..200 {
+            let doc = random_document(&mut rng);
+            seen.extend(
+                doc.elements
+                    .iter()
+                    .map(|e| e.elem.clone().into_elem().kind()),
+            );
+
+            let parsed = Document::from_json(&doc.to_json()).unwrap();
+            assert_eq!(parsed, doc);
+            let scene = parsed.into_scene();
+            assert_eq!(
+                scene.ids(),
+                doc.elements.iter().map(|e| e.id).collect::<Vec<_>>()
+            );
+            assert_eq!(Document::from_scene(&scene), doc);
+        }
+        assert_eq!(seen.len(), 10, "not every kind was generated: {:?}", seen);
+    }
+
+    #[test]
+    fn style_defaults_fill_in_missing_fields() {
+        let doc = Document::from_json(
+            r#"{"version": 1, "canvas": {"width": 10, "height": 10}, "elements": [
+                {"id": 7, "type": "line", "from": [0, 0], "to": [1, 1],
+                 "style": {"width": 5}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(doc.elements[0].id, 7);
+        assert_eq!(
+            doc.elements[0].style,
+            Some(Style {
+                width: 5.,
+                ..Style::default()
+            })
+        );
+    }
+
+    #[test]
+    fn migrate_keeps_a_current_document() {
+        let doc = json!({"version": CURRENT_VERSION, "elements": []});
+        assert_eq!(migrate(doc.clone()).unwrap(), doc);
+        assert_eq!(MIGRATIONS.len() as u32, CURRENT_VERSION - FIRST_VERSION);
+    }
+
+    #[test]
+    fn migrate_refuses_unknown_versions() {
+        let newer = json!({"version": CURRENT_VERSION + 1});
+        assert!(matches!(
+            migrate(newer),
+            Err(DocumentError::UnsupportedVersion(v)) if v == CURRENT_VERSION + 1
+        ));
+        assert!(matches!(
+            migrate(json!({"version": 0})),
+            Err(DocumentError::Invalid(_))
+        ));
+        assert!(matches!(migrate(json!([])), Err(DocumentError::Invalid(_))));
+    }
+
+    #[test]
+    fn migrate_runs_each_step_in_order() {
+        fn to_2(mut doc: Value) -> Result<Value, DocumentError> {
+            doc["version"] = json!(2);
+            doc["steps"] = json!("a");
+            Ok(doc)
+        }
+        fn to_3(mut doc: Value) -> Result<Value, DocumentError> {
+            doc["version"] = json!(3);
+            doc["steps"] = json!(format!("{}b", doc["steps"].as_str().unwrap()));
+            Ok(doc)
+        }
+        fn stuck(doc: Value) -> Result<Value, DocumentError> {
+            Ok(doc)
+        }
+
+        let doc = migrate_with(json!({"version": 1}), &[to_2, to_3]).unwrap();
+        assert_eq!(doc, json!({"version": 3, "steps": "ab"}));
+        let doc = migrate_with(json!({"version": 2, "steps": ""}), &[to_2, to_3]).unwrap();
+        assert_eq!(doc["steps"], "b");
+        assert!(matches!(
+            migrate_with(json!({"version": 1}), &[stuck]),
+            Err(DocumentError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use geo::{coord, Coordinate, EuclideanDistance, LineString, Point};
+
+use super::{begin_stroke, Elem, ElemDoc, ElemRect, Highlight, Painter, Style, GRAB_DISTANCE};
+
+// segments used to approximate the outline for hit testing
+const OUTLINE_STEPS: usize = 64;
+
+// an ellipse inscribed in `bounds`; the handles are the bounding box corners
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElemEllipse {
+    pub bounds: ElemRect,
+    // while set (Shift held) creating and resizing keep a circle
+    constrained: bool,
+}
+
+impl ElemEllipse {
+    pub fn new(center: Coordinate, rx: f64, ry: f64) -> Self {
+        ElemEllipse {
+            bounds: ElemRect::new(
+                coord! {x: center.x - rx, y: center.y - ry},
+                rx * 2.,
+                ry * 2.,
+            ),
+            constrained: false,
+        }
+    }
+
+    pub fn center(&self) -> Coordinate {
+        coord! {
+            x: self.bounds.tl_coord.x + self.bounds.width / 2.,
+            y: self.bounds.tl_coord.y + self.bounds.height / 2.,
+        }
+    }
+
+    pub fn radii(&self) -> (f64, f64) {
+        (self.bounds.width.abs() / 2., self.bounds.height.abs() / 2.)
+    }
+
+    pub fn outline(&self, steps: usize) -> Vec<Coordinate> {
+        let c = self.center();
+        let (rx, ry) = self.radii();
+        (0..=steps)
+            .map(|i| {
+                let a = i as f64 / steps as f64 * std::f64::consts::TAU;
+                coord! {x: c.x + rx * a.cos(), y: c.y + ry * a.sin()}
+            })
+            .collect()
+    }
+}
+
+// moves `end` so the box from `anchor` to it is a square
+fn square_end(anchor: Coordinate, end: Coordinate) -> Coordinate {
+    let (dx, dy) = (end.x - anchor.x, end.y - anchor.y);
+    let side = dx.abs().max(dy.abs());
+    coord! {
+        x: anchor.x + side.copysign(dx),
+        y: anchor.y + side.copysign(dy),
+    }
+}
+
+impl Elem for ElemEllipse {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.bounds.style, highlight);
+        let (rx, ry) = self.radii();
+        painter.ellipse(self.center(), rx, ry);
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        self.bounds.get_vertex()
+    }
+
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let end_coord = if self.constrained {
+            square_end(from_coord, end_coord)
+        } else {
+            end_coord
+        };
+        self.bounds.creating(from_coord, end_coord);
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.bounds.edit_moving(from_coord, end_coord);
+    }
+
+    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        let end_coord = if self.constrained {
+            // the corner across from the dragged one stays put
+            let anchor = self.get_vertex()[((drag_vertex + 2) % 4) as usize];
+            square_end(anchor, end_coord)
+        } else {
+            end_coord
+        };
+        self.bounds
+            .edit_resizing(from_coord, end_coord, drag_vertex);
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        self.bounds.scale(origin, sx, sy);
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        let c = self.center();
+        let (rx, ry) = self.radii();
+        let (dx, dy) = (mouse_point.x() - c.x, mouse_point.y() - c.y);
+        let inside = rx > 0. && ry > 0. && (dx / rx).powi(2) + (dy / ry).powi(2) <= 1.;
+        inside
+            || mouse_point.euclidean_distance(&LineString::from(self.outline(OUTLINE_STEPS)))
+                < GRAB_DISTANCE
+    }
+
+    fn set_constrained(&mut self, constrained: bool) {
+        self.constrained = constrained;
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.bounds.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.bounds.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(*self)
+    }
+
+    fn kind(&self) -> &'static str {
+        "ellipse"
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        let c = self.center();
+        let (rx, ry) = self.radii();
+        ElemDoc::Ellipse {
+            cx: c.x,
+            cy: c.y,
+            rx,
+            ry,
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::mem::size_of;
+
+use geo::{coord, Coordinate, EuclideanDistance, LineString, Point, Simplify};
+
+use super::{
+    begin_stroke, document::to_xy, scale_coord, Elem, ElemDoc, Highlight, Painter, Style,
+    GRAB_DISTANCE,
+};
+
+// Ramer–Douglas–Peucker tolerance applied when the stroke is finished
+pub const SIMPLIFY_EPSILON: f64 = 1.5;
+
+// points generated between two recorded points when smoothing
+const SMOOTH_STEPS: usize = 8;
+
+// a pen stroke. Points are recorded while dragging and thinned out on release.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElemFreehand {
+    pub points: Vec<Coordinate>,
+    // drawn through the points as a Catmull-Rom spline
+    pub smooth: bool,
+    pub style: Style,
+}
+
+impl ElemFreehand {
+    pub fn new(points: Vec<Coordinate>, smooth: bool) -> Self {
+        ElemFreehand {
+            points,
+            smooth,
+            style: Style::default(),
+        }
+    }
+
+    pub fn simplify(&mut self, epsilon: f64) {
+        if self.points.len() > 2 {
+            let line = LineString::from(self.points.clone()).simplify(&epsilon);
+            self.points = line.into_inner();
+        }
+    }
+
+    // what is actually drawn: the points, or the spline through them
+    pub fn outline(&self) -> Vec<Coordinate> {
+        if !self.smooth || self.points.len() < 3 {
+            return self.points.clone();
+        }
+        catmull_rom(&self.points, SMOOTH_STEPS)
+    }
+
+    fn bounds(&self) -> (Coordinate, Coordinate) {
+        let mut min = self.points[0];
+        let mut max = self.points[0];
+        for p in &self.points {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+        (min, max)
+    }
+}
+
+// uniform Catmull-Rom through every point, the ends are repeated
+pub fn catmull_rom(points: &[Coordinate], steps: usize) -> Vec<Coordinate> {
+    let n = points.len();
+    let mut out = vec![points[0]];
+    for i in 0..n - 1 {
+        let p0 = points[i.saturating_sub(1)];
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = points[(i + 2).min(n - 1)];
+        for s in 1..=steps {
+            let t = s as f64 / steps as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            let f = |a: f64, b: f64, c: f64, d: f64| {
+                0.5 * (2. * b
+                    + (c - a) * t
+                    + (2. * a - 5. * b + 4. * c - d) * t2
+                    + (3. * b - a - 3. * c + d) * t3)
+            };
+            out.push(coord! {
+                x: f(p0.x, p1.x, p2.x, p3.x),
+                y: f(p0.y, p1.y, p2.y, p3.y),
+            });
+        }
+    }
+    out
+}
+
+impl Elem for ElemFreehand {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.style, highlight);
+        painter.polyline(&self.outline(), false);
+    }
+
+    // the corners of the bounding box
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        if self.points.is_empty() {
+            return Vec::new();
+        }
+        let (tl, br) = self.bounds();
+        vec![tl, coord! {x: br.x, y: tl.y}, br, coord! {x: tl.x, y: br.y}]
+    }
+
+    // `from_coord` is where the stroke started, every call adds a point
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        if self.points.is_empty() {
+            self.points.push(from_coord);
+        }
+        if self.points.last() != Some(&end_coord) {
+            self.points.push(end_coord);
+        }
+    }
+
+    fn finish_creating(&mut self) {
+        self.simplify(SIMPLIFY_EPSILON);
+    }
+
+    // a click without a drag leaves nothing to see
+    fn is_complete(&self) -> bool {
+        self.points.iter().any(|p| Some(p) != self.points.first())
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let x_dif = end_coord.x - from_coord.x;
+        let y_dif = end_coord.y - from_coord.y;
+        for p in &mut self.points {
+            p.x += x_dif;
+            p.y += y_dif;
+        }
+    }
+
+    // scales the stroke so the dragged corner follows the pointer
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        let vertex = self.get_vertex();
+        if vertex.len() != 4 || !(0..4).contains(&drag_vertex) {
+            return;
+        }
+        let corner = vertex[drag_vertex as usize];
+        let anchor = vertex[((drag_vertex + 2) % 4) as usize];
+        let scale = |from: f64, to: f64| {
+            if from.abs() < 1e-6 {
+                1.
+            } else {
+                // never collapse onto the anchor
+                let to = if to.abs() < 1. {
+                    1f64.copysign(from)
+                } else {
+                    to
+                };
+                to / from
+            }
+        };
+        let sx = scale(corner.x - anchor.x, end_coord.x - anchor.x);
+        let sy = scale(corner.y - anchor.y, end_coord.y - anchor.y);
+        for p in &mut self.points {
+            p.x = anchor.x + (p.x - anchor.x) * sx;
+            p.y = anchor.y + (p.y - anchor.y) * sy;
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        for p in &mut self.points {
+            *p = scale_coord(*p, origin, sx, sy);
+        }
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        match self.points.len() {
+            0 => false,
+            1 => mouse_point.euclidean_distance(&Point::from(self.points[0])) < GRAB_DISTANCE,
+            _ => {
+                mouse_point.euclidean_distance(&LineString::from(self.outline())) < GRAB_DISTANCE
+                    || self
+                        .get_vertex()
+                        .iter()
+                        .any(|v| Point::from(*v).euclidean_distance(&mouse_point) < GRAB_DISTANCE)
+            }
+        }
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(self.clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        "freehand"
+    }
+
+    fn heap_size(&self) -> usize {
+        self.points.len() * size_of::<Coordinate>()
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Freehand {
+            points: self.points.iter().map(|p| to_xy(*p)).collect(),
+            smooth: self.smooth,
+        }
+    }
+}
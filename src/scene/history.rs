@@ -0,0 +1,352 @@
+// Undo/redo for `Scene`. Every user edit is stored as a command that knows
+// how to apply itself in both directions. A drag is recorded once, when the
+// button is released, so all of its steps undo together.
+
+use std::{collections::VecDeque, mem::size_of};
+
+use super::{Elem, ElemId, Scene};
+
+// entries kept before the oldest ones are dropped
+pub const DEFAULT_LIMIT: usize = 200;
+// and the estimated memory they may hold; one step is always kept
+pub const DEFAULT_BYTE_LIMIT: usize = 32 << 20;
+
+// what a stored copy of `elem` costs, roughly. Image pixels and sources
+// are shared between copies and not counted.
+fn elem_size(elem: &dyn Elem) -> usize {
+    size_of::<Box<dyn Elem>>() + std::mem::size_of_val(elem) + elem.heap_size()
+}
+
+#[derive(Clone)]
+pub enum Command {
+    Create {
+        id: ElemId,
+        index: usize,
+        elem: Box<dyn Elem>,
+    },
+    Delete {
+        id: ElemId,
+        index: usize,
+        elem: Box<dyn Elem>,
+    },
+    // moves, resizes and style changes all swap a whole element
+    Edit {
+        id: ElemId,
+        before: Box<dyn Elem>,
+        after: Box<dyn Elem>,
+    },
+    Reorder {
+        id: ElemId,
+        from: usize,
+        to: usize,
+    },
+    // one step for several elements, undone back to front
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    // estimated bytes held by this command
+    pub fn size(&self) -> usize {
+        size_of::<Command>()
+            + match self {
+                Command::Create { elem, .. } | Command::Delete { elem, .. } => {
+                    elem_size(elem.as_ref())
+                }
+                Command::Edit { before, after, .. } => {
+                    elem_size(before.as_ref()) + elem_size(after.as_ref())
+                }
+                Command::Reorder { .. } => 0,
+                Command::Batch(cmds) => cmds.iter().map(Command::size).sum(),
+            }
+    }
+
+    fn undo(&self, scene: &mut Scene) {
+        match self {
+            Command::Create { id, .. } => {
+                scene.remove(*id);
+            }
+            Command::Delete { id, index, elem } => scene.insert(*index, *id, elem.clone()),
+            Command::Edit { id, before, .. } => {
+                if let Some(elem) = scene.get_mut(*id) {
+                    *elem = before.clone();
+                }
+            }
+            Command::Reorder { id, from, .. } => {
+                scene.reorder(*id, *from);
+            }
+            Command::Batch(cmds) => {
+                for cmd in cmds.iter().rev() {
+                    cmd.undo(scene);
+                }
+            }
+        }
+    }
+
+    fn redo(&self, scene: &mut Scene) {
+        match self {
+            Command::Create { id, index, elem } => scene.insert(*index, *id, elem.clone()),
+            Command::Delete { id, .. } => {
+                scene.remove(*id);
+            }
+            Command::Edit { id, after, .. } => {
+                if let Some(elem) = scene.get_mut(*id) {
+                    *elem = after.clone();
+                }
+            }
+            Command::Reorder { id, to, .. } => {
+                scene.reorder(*id, *to);
+            }
+            Command::Batch(cmds) => {
+                for cmd in cmds {
+                    cmd.redo(scene);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct History {
+    undo: VecDeque<Command>,
+    redo: Vec<Command>,
+    limit: usize,
+    byte_limit: usize,
+    // `Command::size` of everything in `undo`
+    bytes: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_LIMIT, DEFAULT_BYTE_LIMIT)
+    }
+}
+
+impl History {
+    pub fn new(limit: usize, byte_limit: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit: limit.max(1),
+            byte_limit,
+            bytes: 0,
+        }
+    }
+
+    pub fn push(&mut self, cmd: Command) {
+        self.redo.clear();
+        self.push_undo(cmd);
+        while self.undo.len() > self.limit || (self.bytes > self.byte_limit && self.undo.len() > 1)
+        {
+            if let Some(cmd) = self.undo.pop_front() {
+                self.bytes -= cmd.size();
+            }
+        }
+    }
+
+    fn push_undo(&mut self, cmd: Command) {
+        self.bytes += cmd.size();
+        self.undo.push_back(cmd);
+    }
+
+    // steps that can be undone
+    pub fn len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+    }
+
+    // returns false when there was nothing to undo
+    pub fn undo(&mut self, scene: &mut Scene) -> bool {
+        match self.undo.pop_back() {
+            Some(cmd) => {
+                self.bytes -= cmd.size();
+                cmd.undo(scene);
+                self.redo.push(cmd);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, scene: &mut Scene) -> bool {
+        match self.redo.pop() {
+            Some(cmd) => {
+                cmd.redo(scene);
+                self.push_undo(cmd);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{coord, Coordinate};
+
+    use super::*;
+    use crate::scene::{ElemFreehand, ElemRect};
+
+    fn rect(x: f64) -> Box<dyn Elem> {
+        Box::new(ElemRect::new(coord! {x: x, y: 10.}, 20., 20.))
+    }
+
+    fn tl(scene: &Scene, id: ElemId) -> Coordinate {
+        scene.bounds(id).unwrap().min()
+    }
+
+    // a drag of many steps, the way the canvas reports it
+    fn drag(scene: &mut Scene, from: Coordinate, steps: &[Coordinate]) {
+        scene.pointer_move(from);
+        scene.pointer_press(from);
+        for step in steps {
+            scene.pointer_drag(*step);
+        }
+        scene.pointer_release();
+    }
+
+    #[test]
+    fn a_drag_is_one_step() {
+        let mut scene = Scene::new();
+        let id = scene.add(rect(10.));
+        let steps = (1..=10)
+            .map(|i| coord! {x: 20. + i as f64, y: 20. + 2. * i as f64})
+            .collect::<Vec<_>>();
+        drag(&mut scene, coord! {x: 20., y: 20.}, &steps);
+        assert_eq!(tl(&scene, id), coord! {x: 20., y: 30.});
+        assert_eq!(scene.history().len(), 2);
+
+        assert!(scene.undo());
+        assert_eq!(tl(&scene, id), coord! {x: 10., y: 10.});
+        assert!(scene.redo());
+        assert_eq!(tl(&scene, id), coord! {x: 20., y: 30.});
+    }
+
+    #[test]
+    fn a_click_without_movement_is_not_recorded() {
+        let mut scene = Scene::new();
+        let id = scene.add(rect(10.));
+        drag(&mut scene, coord! {x: 20., y: 20.}, &[]);
+        assert_eq!(scene.selection(), &[id]);
+        assert_eq!(scene.history().len(), 1);
+    }
+
+    #[test]
+    fn undoes_and_redoes_create_delete_and_reorder() {
+        let mut scene = Scene::new();
+        let a = scene.add(rect(10.));
+        let b = scene.add(rect(50.));
+        assert!(scene.send_to_back(b));
+        assert!(scene.delete(a));
+        assert_eq!(scene.ids(), [b]);
+
+        assert!(scene.undo());
+        assert_eq!(scene.ids(), [b, a]);
+        assert!(scene.undo());
+        assert_eq!(scene.ids(), [a, b]);
+        assert!(scene.undo());
+        assert!(scene.undo());
+        assert!(scene.is_empty());
+        assert!(!scene.undo());
+
+        while scene.redo() {}
+        assert_eq!(scene.ids(), [b]);
+        assert_eq!(tl(&scene, b), coord! {x: 50., y: 10.});
+    }
+
+    #[test]
+    fn a_new_step_drops_the_redo_steps() {
+        let mut scene = Scene::new();
+        scene.add(rect(10.));
+        scene.add(rect(50.));
+        assert!(scene.undo());
+        assert!(scene.history().can_redo());
+        scene.add(rect(90.));
+        assert!(!scene.history().can_redo());
+        assert_eq!(scene.len(), 2);
+    }
+
+    #[test]
+    fn drops_the_oldest_steps_past_the_limit() {
+        let mut scene = Scene::new();
+        let mut history = History::new(2, usize::MAX);
+        for x in [10., 50., 90.] {
+            let elem = rect(x);
+            let index = scene.len();
+            let id = scene.push(elem.clone());
+            history.push(Command::Create { id, index, elem });
+        }
+        assert_eq!(history.len(), 2);
+        assert!(history.undo(&mut scene));
+        assert!(history.undo(&mut scene));
+        assert!(!history.undo(&mut scene));
+        assert_eq!(scene.len(), 1);
+    }
+
+    #[test]
+    fn drops_the_oldest_steps_past_the_byte_limit() {
+        let points = (0..1000)
+            .map(|i| coord! {x: i as f64, y: 0.})
+            .collect::<Vec<_>>();
+        let stroke: Box<dyn Elem> = Box::new(ElemFreehand::new(points, false));
+        let one = Command::Create {
+            id: 1,
+            index: 0,
+            elem: stroke.clone(),
+        }
+        .size();
+        assert!(one > 1000 * size_of::<Coordinate>());
+
+        let mut history = History::new(100, one * 3);
+        for id in 1..=10 {
+            history.push(Command::Create {
+                id,
+                index: 0,
+                elem: stroke.clone(),
+            });
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.bytes(), one * 3);
+
+        // a single step over the limit is still kept
+        let mut history = History::new(100, 1);
+        history.push(Command::Create {
+            id: 1,
+            index: 0,
+            elem: stroke,
+        });
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn tracks_bytes_through_undo_and_redo() {
+        let mut scene = Scene::new();
+        scene.add(rect(10.));
+        scene.add(rect(50.));
+        let bytes = scene.history().bytes();
+        assert!(scene.undo());
+        assert!(scene.history().bytes() < bytes);
+        assert!(scene.redo());
+        assert_eq!(scene.history().bytes(), bytes);
+    }
+}
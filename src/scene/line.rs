@@ -0,0 +1,94 @@
+use geo::{Coordinate, EuclideanDistance, Line, Point};
+
+use super::{
+    begin_stroke, document::to_xy, scale_coord, Elem, ElemDoc, Highlight, Painter, Style,
+    GRAB_DISTANCE,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElemLine {
+    pub from_coord: Coordinate,
+    pub end_coord: Coordinate,
+    pub style: Style,
+}
+
+impl ElemLine {
+    pub fn new(from_coord: Coordinate, end_coord: Coordinate) -> Self {
+        ElemLine {
+            from_coord,
+            end_coord,
+            style: Style::default(),
+        }
+    }
+}
+
+impl Elem for ElemLine {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.style, highlight);
+        painter.line(self.from_coord, self.end_coord);
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        vec![self.from_coord, self.end_coord]
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        let t_line = Line::new(self.from_coord, self.end_coord);
+        mouse_point.euclidean_distance(&t_line) < GRAB_DISTANCE
+    }
+
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.from_coord = from_coord;
+        self.end_coord = end_coord;
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let x_dif = end_coord.x - from_coord.x;
+        let y_dif = end_coord.y - from_coord.y;
+
+        self.from_coord.x += x_dif;
+        self.from_coord.y += y_dif;
+        self.end_coord.x += x_dif;
+        self.end_coord.y += y_dif;
+    }
+
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        match drag_vertex {
+            0 => {
+                self.from_coord = end_coord;
+            }
+            1 => {
+                self.end_coord = end_coord;
+            }
+            _ => (),
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        self.from_coord = scale_coord(self.from_coord, origin, sx, sy);
+        self.end_coord = scale_coord(self.end_coord, origin, sx, sy);
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(*self)
+    }
+
+    fn kind(&self) -> &'static str {
+        "line"
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Line {
+            from: to_xy(self.from_coord),
+            to: to_xy(self.end_coord),
+        }
+    }
+}
@@ -0,0 +1,1153 @@
+// The drawing model behind examples/demo9.rs. Nothing in here knows about
+// FLTK: elements paint themselves through `Painter`, and the canvas forwards
+// pointer events to `Scene`.
+
+mod arrow;
+mod bezier;
+pub mod document;
+mod ellipse;
+mod freehand;
+pub mod history;
+mod line;
+mod picture;
+mod polyline;
+pub mod raster;
+mod rect;
+mod style;
+pub mod svg;
+mod text;
+
+pub use arrow::{ElemArrow, Head};
+pub use bezier::{ElemBezier, Node, NodeDoc};
+pub use document::ElemDoc;
+pub use ellipse::ElemEllipse;
+pub use freehand::ElemFreehand;
+pub use history::{Command, History};
+pub use line::ElemLine;
+pub use picture::ElemImage;
+pub use polyline::ElemPolyline;
+pub use rect::ElemRect;
+pub use style::{Cap, Dash, Join, Style};
+pub use text::{measure_line, Align, ElemText, FontFamily, TextStyle};
+
+use geo::{coord, Coordinate, EuclideanDistance, Point, Rect};
+
+pub type Color = [u8; 4];
+
+pub const RED: Color = [0xff, 0, 0, 0xff];
+pub const DARK_RED: Color = [0x80, 0, 0, 0xff];
+
+// distance in canvas units within which the pointer grabs a vertex
+pub const GRAB_DISTANCE: f64 = 10.;
+
+pub trait Painter {
+    // applies to everything drawn until the next call; the fill is used by
+    // rect, ellipse and closed polylines and beziers
+    fn set_style(&mut self, style: &Style);
+    fn set_stroke(&mut self, color: Color, width: f64) {
+        self.set_style(&Style::solid(color, width));
+    }
+    fn line(&mut self, from: Coordinate, to: Coordinate);
+    fn rect(&mut self, tl: Coordinate, width: f64, height: f64);
+    fn ellipse(&mut self, center: Coordinate, rx: f64, ry: f64);
+    fn polyline(&mut self, points: &[Coordinate], closed: bool);
+    // quadratic curve from `from` to `to`
+    fn quad(&mut self, from: Coordinate, control: Coordinate, to: Coordinate);
+    // cubic segments: the start point, then control, control and end point
+    // of each segment
+    fn bezier(&mut self, points: &[Coordinate], closed: bool);
+    // filled with the stroke color, for arrow heads and markers
+    fn fill_polygon(&mut self, points: &[Coordinate]);
+    fn text(&mut self, text: &ElemText);
+    // only called when the pixels are loaded
+    fn image(&mut self, image: &ElemImage);
+    // the round grip drawn on a vertex of a selected element
+    fn handle(&mut self, center: Coordinate, color: Color);
+}
+
+pub trait Elem {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight);
+    fn get_vertex(&self) -> Vec<Coordinate>;
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate);
+    // the creating drag ended, or the last vertex was placed
+    fn finish_creating(&mut self) {}
+    // built by clicking vertices instead of one drag
+    fn placed_by_clicks(&self) -> bool {
+        false
+    }
+    fn place_vertex(&mut self, _coord: Coordinate) {}
+    // the pointer while placing, `None` when done
+    fn preview_vertex(&mut self, _coord: Option<Coordinate>) {}
+    // false drops the element when creating ends
+    fn is_complete(&self) -> bool {
+        true
+    }
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate);
+    // a handle was pressed; returns the vertex index the drag should move
+    fn grab_vertex(&mut self, index: i32) -> i32 {
+        index
+    }
+    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32);
+    // stretches away from `origin`, for sizes typed in by hand
+    fn scale(&mut self, _origin: Coordinate, _sx: f64, _sy: f64) {}
+    fn remove_vertex(&mut self, _index: i32) -> bool {
+        false
+    }
+    // the words of a text element
+    fn text(&self) -> Option<&str> {
+        None
+    }
+    fn set_text(&mut self, _text: &str) -> bool {
+        false
+    }
+    fn hover_condition(&self, mouse_point: Point) -> bool;
+    // stroke and fill; None for elements that look after their own colors
+    fn style(&self) -> Option<&Style> {
+        None
+    }
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        None
+    }
+    // Shift held: keep proportions while creating or resizing
+    fn set_constrained(&mut self, _constrained: bool) {}
+    // the picture behind an image element
+    fn image(&self) -> Option<&ElemImage> {
+        None
+    }
+    fn box_clone(&self) -> Box<dyn Elem>;
+    // the `type` of its saved form, without building it
+    fn kind(&self) -> &'static str;
+    // bytes it owns beyond its own size, for the undo budget
+    fn heap_size(&self) -> usize {
+        0
+    }
+    fn to_doc(&self) -> ElemDoc;
+}
+
+impl Clone for Box<dyn Elem> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+// how the canvas marks an element; exports draw everything plain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    None,
+    Hover,
+    Selected,
+}
+
+// grips on every vertex of a selected element, then its highlighted style
+pub fn begin_stroke(
+    painter: &mut dyn Painter,
+    vertex: &[Coordinate],
+    style: &Style,
+    highlight: Highlight,
+) {
+    if highlight == Highlight::Selected {
+        for v in vertex {
+            painter.handle(*v, DARK_RED);
+        }
+    }
+    painter.set_style(&style.highlighted(highlight));
+}
+
+pub fn scale_coord(c: Coordinate, origin: Coordinate, sx: f64, sy: f64) -> Coordinate {
+    coord! {
+        x: origin.x + (c.x - origin.x) * sx,
+        y: origin.y + (c.y - origin.y) * sy,
+    }
+}
+
+// what an undo step compares: the saved form and the style. Pictures are
+// compared without copying out their source, which can be megabytes.
+fn same_elem(a: &dyn Elem, b: &dyn Elem) -> bool {
+    match (a.image(), b.image()) {
+        (Some(a), Some(b)) => a.same_picture(b),
+        (None, None) => a.to_doc() == b.to_doc() && a.style() == b.style(),
+        _ => false,
+    }
+}
+
+// index of the closest vertex within grab distance
+fn nearest_vertex(vertex: &[Coordinate], point: Point) -> Option<i32> {
+    vertex
+        .iter()
+        .map(|v| point.euclidean_distance(&Point::from(*v)))
+        .enumerate()
+        .filter(|(_, d)| *d < GRAB_DISTANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i as i32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Creating,
+    Placing,
+    EditMoving, // default
+    EditResizing,
+    Deleting,
+}
+
+pub type ElemId = u64;
+
+#[derive(Clone)]
+pub struct Scene {
+    // back to front
+    elems: Vec<(ElemId, Box<dyn Elem>)>,
+    next_id: ElemId,
+    hover: Option<ElemId>,
+    // in the order they were picked, kept until cleared
+    selected: Vec<ElemId>,
+    status: Status,
+    drag_vertex: i32,
+    press_coord: Coordinate,
+    last_coord: Coordinate,
+    // width, height of the drawing area
+    canvas_size: (u32, u32),
+    history: History,
+    // the elements the current drag changes, as they were when it started
+    drag_before: Vec<(ElemId, Box<dyn Elem>)>,
+    constrained: bool,
+    // last known pointer position
+    pointer: Coordinate,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            elems: Vec::new(),
+            next_id: 1,
+            hover: None,
+            selected: Vec::new(),
+            status: Status::EditMoving,
+            drag_vertex: 0,
+            press_coord: Coordinate { x: 0., y: 0. },
+            last_coord: Coordinate { x: 0., y: 0. },
+            canvas_size: (500, 500),
+            history: History::default(),
+            drag_before: Vec::new(),
+            constrained: false,
+            pointer: Coordinate { x: 0., y: 0. },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    // back to front
+    pub fn iter(&self) -> impl Iterator<Item = (ElemId, &dyn Elem)> {
+        self.elems.iter().map(|(id, elem)| (*id, elem.as_ref()))
+    }
+
+    pub fn ids(&self) -> Vec<ElemId> {
+        self.elems.iter().map(|(id, _)| *id).collect()
+    }
+
+    pub fn get(&self, id: ElemId) -> Option<&dyn Elem> {
+        self.index_of(id).map(|i| self.elems[i].1.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: ElemId) -> Option<&mut Box<dyn Elem>> {
+        let i = self.index_of(id)?;
+        Some(&mut self.elems[i].1)
+    }
+
+    pub fn index_of(&self, id: ElemId) -> Option<usize> {
+        self.elems.iter().position(|(eid, _)| *eid == id)
+    }
+
+    pub fn canvas_size(&self) -> (u32, u32) {
+        self.canvas_size
+    }
+
+    pub fn set_canvas_size(&mut self, width: u32, height: u32) {
+        self.canvas_size = (width, height);
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn hover(&self) -> Option<ElemId> {
+        self.hover
+    }
+
+    pub fn pointer(&self) -> Coordinate {
+        self.pointer
+    }
+
+    pub fn selection(&self) -> &[ElemId] {
+        &self.selected
+    }
+
+    pub fn is_selected(&self, id: ElemId) -> bool {
+        self.selected.contains(&id)
+    }
+
+    // `extend` toggles `id` in the selection, otherwise it is all that is
+    // selected
+    pub fn select(&mut self, id: ElemId, extend: bool) {
+        if !extend {
+            self.selected = vec![id];
+        } else if let Some(i) = self.selected.iter().position(|s| *s == id) {
+            self.selected.remove(i);
+        } else {
+            self.selected.push(id);
+        }
+    }
+
+    // returns false when nothing was selected
+    pub fn clear_selection(&mut self) -> bool {
+        let had = !self.selected.is_empty();
+        self.selected.clear();
+        had
+    }
+
+    // drops ids that are gone after an undo or a removal
+    fn prune(&mut self) {
+        let elems = &self.elems;
+        let exists = |id: &ElemId| elems.iter().any(|(eid, _)| eid == id);
+        self.hover = self.hover.filter(exists);
+        self.selected.retain(exists);
+    }
+
+    // raw edits from here to `send_to_back` are not recorded in the history
+
+    // adds on top and returns the new id
+    pub fn push(&mut self, elem: Box<dyn Elem>) -> ElemId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.elems.push((id, elem));
+        id
+    }
+
+    // puts an element back at a given z position, keeping its id
+    pub fn insert(&mut self, index: usize, id: ElemId, elem: Box<dyn Elem>) {
+        let index = index.min(self.elems.len());
+        self.elems.insert(index, (id, elem));
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    pub fn remove(&mut self, id: ElemId) -> Option<Box<dyn Elem>> {
+        let i = self.index_of(id)?;
+        let elem = self.elems.remove(i).1;
+        self.prune();
+        Some(elem)
+    }
+
+    pub fn clear(&mut self) {
+        self.elems.clear();
+        self.hover = None;
+        self.selected.clear();
+        self.status = Status::EditMoving;
+        self.history.clear();
+    }
+
+    // moves an element to z position `index`, 0 is the back
+    pub fn reorder(&mut self, id: ElemId, index: usize) -> bool {
+        match self.index_of(id) {
+            Some(i) => {
+                let elem = self.elems.remove(i);
+                let index = index.min(self.elems.len());
+                self.elems.insert(index, elem);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -- undoable edits
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn record(&mut self, cmd: Command) {
+        self.history.push(cmd);
+    }
+
+    // several commands as one step, a single one as it is
+    fn record_all(&mut self, mut cmds: Vec<Command>) -> bool {
+        match cmds.len() {
+            0 => false,
+            1 => {
+                self.record(cmds.remove(0));
+                true
+            }
+            _ => {
+                self.record(Command::Batch(cmds));
+                true
+            }
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let mut history = std::mem::take(&mut self.history);
+        let done = history.undo(self);
+        self.history = history;
+        self.prune();
+        done
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let mut history = std::mem::take(&mut self.history);
+        let done = history.redo(self);
+        self.history = history;
+        self.prune();
+        done
+    }
+
+    // `push` that can be undone
+    pub fn add(&mut self, elem: Box<dyn Elem>) -> ElemId {
+        let index = self.elems.len();
+        let id = self.push(elem.clone());
+        self.record(Command::Create { id, index, elem });
+        id
+    }
+
+    // `remove` that can be undone
+    pub fn delete(&mut self, id: ElemId) -> bool {
+        let index = match self.index_of(id) {
+            Some(index) => index,
+            None => return false,
+        };
+        let elem = self.remove(id).unwrap();
+        self.record(Command::Delete { id, index, elem });
+        true
+    }
+
+    // changes one element in place, recorded as a single step
+    pub fn edit(&mut self, id: ElemId, f: impl FnOnce(&mut Box<dyn Elem>)) -> bool {
+        let elem = match self.get_mut(id) {
+            Some(elem) => elem,
+            None => return false,
+        };
+        let before = elem.clone();
+        f(elem);
+        let after = elem.clone();
+        if !same_elem(before.as_ref(), after.as_ref()) {
+            self.record(Command::Edit { id, before, after });
+        }
+        true
+    }
+
+    // `delete` for every selected element, undone together
+    pub fn delete_selection(&mut self) -> bool {
+        let mut cmds = Vec::new();
+        for id in self.selected.clone() {
+            if let Some(index) = self.index_of(id) {
+                let elem = self.remove(id).unwrap();
+                cmds.push(Command::Delete { id, index, elem });
+            }
+        }
+        self.record_all(cmds)
+    }
+
+    // `edit` for every selected element, undone together
+    pub fn edit_selection(&mut self, mut f: impl FnMut(&mut Box<dyn Elem>)) -> bool {
+        let mut cmds = Vec::new();
+        for id in self.selected.clone() {
+            if let Some(elem) = self.get_mut(id) {
+                let before = elem.clone();
+                f(elem);
+                let after = elem.clone();
+                if !same_elem(before.as_ref(), after.as_ref()) {
+                    cmds.push(Command::Edit { id, before, after });
+                }
+            }
+        }
+        self.record_all(cmds)
+    }
+
+    // the selected elements to the front or the back, keeping their order
+    // among themselves
+    pub fn restack_selection(&mut self, front: bool) -> bool {
+        let mut ids: Vec<ElemId> = self
+            .ids()
+            .into_iter()
+            .filter(|id| self.is_selected(*id))
+            .collect();
+        if ids.is_empty() {
+            return false;
+        }
+        if !front {
+            ids.reverse();
+        }
+        let to = if front { self.elems.len() - 1 } else { 0 };
+        let mut cmds = Vec::new();
+        for id in ids {
+            let from = self.index_of(id).unwrap();
+            if from != to {
+                self.reorder(id, to);
+                cmds.push(Command::Reorder { id, from, to });
+            }
+        }
+        self.record_all(cmds)
+    }
+
+    fn restack(&mut self, id: ElemId, to: usize) -> bool {
+        let from = match self.index_of(id) {
+            Some(from) => from,
+            None => return false,
+        };
+        let to = to.min(self.elems.len() - 1);
+        if from != to {
+            self.reorder(id, to);
+            self.record(Command::Reorder { id, from, to });
+        }
+        true
+    }
+
+    pub fn bring_to_front(&mut self, id: ElemId) -> bool {
+        self.restack(id, usize::MAX)
+    }
+
+    pub fn send_to_back(&mut self, id: ElemId) -> bool {
+        self.restack(id, 0)
+    }
+
+    // topmost element under the point; the handles of selected elements
+    // count as part of them even where they sit off the shape
+    pub fn hit_test(&self, point: Point) -> Option<ElemId> {
+        let on_handle = |id: &ElemId, elem: &dyn Elem| {
+            self.is_selected(*id) && nearest_vertex(&elem.get_vertex(), point).is_some()
+        };
+        let mut topmost = self.elems.iter().rev();
+        topmost
+            .clone()
+            .find(|(id, elem)| on_handle(id, elem.as_ref()))
+            .or_else(|| topmost.find(|(_, elem)| elem.hover_condition(point)))
+            .map(|(id, _)| *id)
+    }
+
+    // the box around an element's handles
+    pub fn bounds(&self, id: ElemId) -> Option<Rect> {
+        let vertex = self.get(id)?.get_vertex();
+        let first = *vertex.first()?;
+        let (tl, br) = vertex.iter().fold((first, first), |(tl, br), v| {
+            (
+                coord! {x: tl.x.min(v.x), y: tl.y.min(v.y)},
+                coord! {x: br.x.max(v.x), y: br.y.max(v.y)},
+            )
+        });
+        Some(Rect::new(tl, br))
+    }
+
+    // moves and stretches an element onto `to`, as one undo step; a side
+    // that is flat (a level line) keeps its size
+    pub fn set_bounds(&mut self, id: ElemId, to: Rect) -> bool {
+        let from = match self.bounds(id) {
+            Some(from) => from,
+            None => return false,
+        };
+        let ratio = |old: f64, new: f64| {
+            if old < 1e-6 || new < 1. {
+                1.
+            } else {
+                new / old
+            }
+        };
+        let sx = ratio(from.width(), to.width());
+        let sy = ratio(from.height(), to.height());
+        self.edit(id, |elem| {
+            elem.scale(from.min(), sx, sy);
+            elem.edit_moving(from.min(), to.min());
+        })
+    }
+
+    pub fn paint(&self, painter: &mut dyn Painter) {
+        for (id, elem) in &self.elems {
+            let highlight = if self.is_selected(*id) {
+                Highlight::Selected
+            } else if Some(*id) == self.hover {
+                Highlight::Hover
+            } else {
+                Highlight::None
+            };
+            elem.draw(painter, highlight);
+        }
+    }
+
+    // the next drag creates `elem`
+    pub fn start_creating(&mut self, elem: Box<dyn Elem>) -> ElemId {
+        self.status = if elem.placed_by_clicks() {
+            Status::Placing
+        } else {
+            Status::Creating
+        };
+        self.push(elem)
+    }
+
+    // double click or Enter while placing vertices
+    pub fn finish_placing(&mut self) {
+        if self.status != Status::Placing {
+            return;
+        }
+        self.status = Status::EditMoving;
+        if let Some((id, elem)) = self.elems.last_mut() {
+            elem.preview_vertex(None);
+            elem.finish_creating();
+            let (id, elem) = (*id, elem.clone());
+            if elem.is_complete() {
+                let index = self.elems.len() - 1;
+                self.record(Command::Create { id, index, elem });
+                self.select(id, false);
+            } else {
+                self.remove(id);
+            }
+        }
+    }
+
+    // the vertex handle of the topmost element, if selected, within grab
+    // distance
+    pub fn vertex_at(&self, coord: Coordinate) -> Option<(ElemId, i32)> {
+        let mouse_point = Point::from(coord);
+        let id = self
+            .hit_test(mouse_point)
+            .filter(|id| self.is_selected(*id))?;
+        nearest_vertex(&self.get(id)?.get_vertex(), mouse_point).map(|i| (id, i))
+    }
+
+    // Delete key or right click on a vertex
+    pub fn delete_vertex_at(&mut self, coord: Coordinate) -> bool {
+        match self.vertex_at(coord) {
+            Some((id, index)) => {
+                let mut removed = false;
+                self.edit(id, |elem| removed = elem.remove_vertex(index));
+                removed
+            }
+            None => false,
+        }
+    }
+
+    // the next press removes the element under the pointer
+    pub fn start_deleting(&mut self) {
+        self.status = Status::Deleting;
+    }
+
+    // pointer moved without a button, returns true when the hover changed
+    pub fn pointer_move(&mut self, coord: Coordinate) -> bool {
+        self.pointer = coord;
+        if self.status == Status::Placing {
+            if let Some((_, elem)) = self.elems.last_mut() {
+                elem.preview_vertex(Some(coord));
+            }
+            return true;
+        }
+        let hover = self.hit_test(Point::from(coord));
+        let changed = hover != self.hover;
+        self.hover = hover;
+        changed
+    }
+
+    pub fn pointer_press(&mut self, coord: Coordinate) {
+        self.press_coord = coord;
+        self.last_coord = coord;
+        if self.status == Status::Placing {
+            if let Some((_, elem)) = self.elems.last_mut() {
+                elem.place_vertex(coord);
+            }
+            return;
+        }
+        let id = match self.hover {
+            Some(id) => id,
+            // a click on empty canvas drops the selection, Shift keeps it
+            None => {
+                if self.status == Status::EditMoving && !self.constrained {
+                    self.clear_selection();
+                }
+                return;
+            }
+        };
+        match self.status {
+            Status::Deleting => {
+                self.delete(id);
+                self.status = Status::EditMoving;
+            }
+            // Shift-click adds or takes away; a click on a selected element
+            // keeps the others so it can be moved without losing them
+            Status::EditMoving => {
+                let was_selected = self.is_selected(id);
+                if self.constrained || !was_selected {
+                    self.select(id, self.constrained);
+                }
+                // handles are only shown, and grabbed, on selected elements
+                let vertex = match self.get(id) {
+                    Some(elem) if was_selected => elem.get_vertex(),
+                    _ => Vec::new(),
+                };
+                // taken before the grab, which may split a segment, so the
+                // split is undone with the drag
+                let before = self.get(id).map(|e| e.box_clone());
+                let resizing = nearest_vertex(&vertex, Point::from(coord))
+                    .and_then(|i| self.get_mut(id).map(|elem| elem.grab_vertex(i)));
+                // a resize takes one element, a move the whole selection
+                self.drag_before = match resizing {
+                    Some(v) => {
+                        self.drag_vertex = v;
+                        self.status = Status::EditResizing;
+                        before.map(|e| vec![(id, e)]).unwrap_or_default()
+                    }
+                    None => {
+                        let ids = if self.is_selected(id) {
+                            self.selected.clone()
+                        } else {
+                            vec![id]
+                        };
+                        ids.into_iter()
+                            .filter_map(|id| self.get(id).map(|e| (id, e.box_clone())))
+                            .collect()
+                    }
+                };
+            }
+            Status::Creating | Status::Placing | Status::EditResizing => (),
+        }
+    }
+
+    // the canvas reports Shift here before pressing or dragging
+    pub fn set_constrained(&mut self, constrained: bool) {
+        self.constrained = constrained;
+    }
+
+    pub fn pointer_drag(&mut self, coord: Coordinate) {
+        self.pointer = coord;
+        let (press, last, drag_vertex) = (self.press_coord, self.last_coord, self.drag_vertex);
+        let constrained = self.constrained;
+        match self.status {
+            Status::Creating | Status::Placing => {
+                if let Some((_, elem)) = self.elems.last_mut() {
+                    elem.set_constrained(constrained);
+                    elem.creating(press, coord);
+                }
+            }
+            Status::EditMoving => {
+                let ids = self
+                    .drag_before
+                    .iter()
+                    .map(|(id, _)| *id)
+                    .collect::<Vec<_>>();
+                for id in ids {
+                    if let Some(elem) = self.get_mut(id) {
+                        elem.edit_moving(last, coord);
+                    }
+                }
+                self.last_coord = coord;
+            }
+            // the element grabbed on press, even once the pointer leaves it
+            Status::EditResizing => {
+                let id = self.drag_before.first().map(|(id, _)| *id);
+                if let Some(elem) = id.and_then(|id| self.get_mut(id)) {
+                    elem.set_constrained(constrained);
+                    elem.edit_resizing(press, coord, drag_vertex);
+                }
+            }
+            Status::Deleting => (),
+        }
+    }
+
+    pub fn pointer_release(&mut self) {
+        match self.status {
+            Status::Creating => {
+                if let Some((id, elem)) = self.elems.last_mut() {
+                    elem.finish_creating();
+                    let (id, elem) = (*id, elem.clone());
+                    if elem.is_complete() {
+                        let index = self.elems.len() - 1;
+                        self.record(Command::Create { id, index, elem });
+                        self.select(id, false);
+                    } else {
+                        self.remove(id);
+                    }
+                }
+            }
+            Status::EditMoving | Status::EditResizing => {
+                let mut cmds = Vec::new();
+                for (id, before) in std::mem::take(&mut self.drag_before) {
+                    let after = match self.get(id) {
+                        Some(elem) => elem.box_clone(),
+                        None => continue,
+                    };
+                    // a click without movement is not worth an undo step
+                    if !same_elem(before.as_ref(), after.as_ref()) {
+                        cmds.push(Command::Edit { id, before, after });
+                    }
+                }
+                self.record_all(cmds);
+            }
+            // vertices keep coming until `finish_placing`, or until the
+            // element says it is done (a path closed on its first anchor)
+            Status::Placing => {
+                let done = self
+                    .elems
+                    .last()
+                    .is_some_and(|(_, elem)| !elem.placed_by_clicks());
+                if done {
+                    self.finish_placing();
+                }
+                return;
+            }
+            Status::Deleting => (),
+        }
+        self.drag_before.clear();
+        self.status = Status::EditMoving;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drag(scene: &mut Scene, from: Coordinate, to: Coordinate) {
+        scene.pointer_move(from);
+        scene.pointer_press(from);
+        scene.pointer_drag(to);
+        scene.pointer_release();
+    }
+
+    fn create(scene: &mut Scene, elem: Box<dyn Elem>, from: Coordinate, to: Coordinate) -> ElemId {
+        let id = scene.start_creating(elem);
+        scene.pointer_press(from);
+        scene.pointer_drag(to);
+        scene.pointer_release();
+        id
+    }
+
+    fn new_line() -> Box<dyn Elem> {
+        Box::new(ElemLine::new(coord! {x: 0., y: 0.}, coord! {x: 0., y: 0.}))
+    }
+
+    fn new_rect() -> Box<dyn Elem> {
+        Box::new(ElemRect::new(coord! {x: 0., y: 0.}, 0., 0.))
+    }
+
+    #[test]
+    fn creates_a_line() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_line(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 100., y: 50.},
+        );
+        assert_eq!(
+            scene.get(id).unwrap().get_vertex(),
+            vec![coord! {x: 10., y: 10.}, coord! {x: 100., y: 50.}]
+        );
+        assert_eq!(scene.selection(), &[id]);
+        assert_eq!(scene.status(), Status::EditMoving);
+        assert!(scene.history().can_undo());
+    }
+
+    #[test]
+    fn creates_a_rect_from_any_corner() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 80., y: 60.},
+            coord! {x: 20., y: 10.},
+        );
+        let bounds = scene.bounds(id).unwrap();
+        assert_eq!(bounds.min(), coord! {x: 20., y: 10.});
+        assert_eq!(bounds.max(), coord! {x: 80., y: 60.});
+    }
+
+    #[test]
+    fn drops_an_incomplete_element() {
+        let mut scene = Scene::new();
+        // a drag does not add points to a shape that was not started by clicks
+        let elem = Box::new(ElemPolyline::new(Vec::new(), false));
+        let id = create(
+            &mut scene,
+            elem,
+            coord! {x: 10., y: 10.},
+            coord! {x: 50., y: 50.},
+        );
+        assert!(scene.get(id).is_none());
+        assert!(scene.selection().is_empty());
+        assert!(!scene.history().can_undo());
+    }
+
+    #[test]
+    fn drops_a_pen_click_without_a_stroke() {
+        let mut scene = Scene::new();
+        let pen = Box::new(ElemFreehand::new(Vec::new(), true));
+        let at = coord! {x: 40., y: 40.};
+        let id = create(&mut scene, pen, at, at);
+        assert!(scene.get(id).is_none());
+        assert!(!scene.history().can_undo());
+
+        let pen = Box::new(ElemFreehand::new(Vec::new(), true));
+        let id = create(&mut scene, pen, at, coord! {x: 80., y: 40.});
+        assert!(scene.get(id).is_some());
+        assert_eq!(scene.history().len(), 1);
+    }
+
+    #[test]
+    fn hovers_a_line_near_its_stroke() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_line(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 110., y: 10.},
+        );
+        assert!(scene.pointer_move(coord! {x: 60., y: 12.}));
+        assert_eq!(scene.hover(), Some(id));
+        assert!(scene.pointer_move(coord! {x: 60., y: 40.}));
+        assert_eq!(scene.hover(), None);
+        assert!(!scene.pointer_move(coord! {x: 60., y: 50.}));
+    }
+
+    #[test]
+    fn hovers_a_rect_inside() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 60., y: 60.},
+        );
+        scene.pointer_move(coord! {x: 30., y: 40.});
+        assert_eq!(scene.hover(), Some(id));
+        scene.pointer_move(coord! {x: 90., y: 90.});
+        assert_eq!(scene.hover(), None);
+    }
+
+    #[test]
+    fn moves_a_line() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_line(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 110., y: 10.},
+        );
+        drag(&mut scene, coord! {x: 60., y: 10.}, coord! {x: 70., y: 30.});
+        assert_eq!(
+            scene.get(id).unwrap().get_vertex(),
+            vec![coord! {x: 20., y: 30.}, coord! {x: 120., y: 30.}]
+        );
+        assert!(scene.undo());
+        assert_eq!(
+            scene.get(id).unwrap().get_vertex(),
+            vec![coord! {x: 10., y: 10.}, coord! {x: 110., y: 10.}]
+        );
+    }
+
+    #[test]
+    fn moves_a_rect() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 60., y: 60.},
+        );
+        drag(&mut scene, coord! {x: 30., y: 30.}, coord! {x: 40., y: 20.});
+        let bounds = scene.bounds(id).unwrap();
+        assert_eq!(bounds.min(), coord! {x: 20., y: 0.});
+        assert_eq!(bounds.max(), coord! {x: 70., y: 50.});
+    }
+
+    #[test]
+    fn resizes_a_line_by_its_end() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_line(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 110., y: 10.},
+        );
+        drag(
+            &mut scene,
+            coord! {x: 110., y: 10.},
+            coord! {x: 150., y: 90.},
+        );
+        assert_eq!(
+            scene.get(id).unwrap().get_vertex(),
+            vec![coord! {x: 10., y: 10.}, coord! {x: 150., y: 90.}]
+        );
+    }
+
+    #[test]
+    fn resizes_a_rect_by_a_corner() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 60., y: 60.},
+        );
+        drag(
+            &mut scene,
+            coord! {x: 60., y: 60.},
+            coord! {x: 100., y: 80.},
+        );
+        let bounds = scene.bounds(id).unwrap();
+        assert_eq!(bounds.min(), coord! {x: 10., y: 10.});
+        assert_eq!(bounds.max(), coord! {x: 100., y: 80.});
+    }
+
+    #[test]
+    fn moves_the_whole_selection_as_one_step() {
+        let mut scene = Scene::new();
+        let line = create(
+            &mut scene,
+            new_line(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 110., y: 10.},
+        );
+        let rect = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 50.},
+            coord! {x: 60., y: 100.},
+        );
+        scene.select(line, true);
+        drag(&mut scene, coord! {x: 30., y: 70.}, coord! {x: 40., y: 90.});
+        assert_eq!(scene.selection(), &[rect, line]);
+        assert_eq!(
+            scene.get(line).unwrap().get_vertex(),
+            vec![coord! {x: 20., y: 30.}, coord! {x: 120., y: 30.}]
+        );
+        assert_eq!(scene.bounds(rect).unwrap().min(), coord! {x: 20., y: 70.});
+        assert!(scene.undo());
+        assert_eq!(
+            scene.get(line).unwrap().get_vertex(),
+            vec![coord! {x: 10., y: 10.}, coord! {x: 110., y: 10.}]
+        );
+        assert_eq!(scene.bounds(rect).unwrap().min(), coord! {x: 10., y: 50.});
+    }
+
+    #[test]
+    fn grabs_an_ellipse_by_its_corner_only_when_selected() {
+        let mut scene = Scene::new();
+        let id = scene.add(Box::new(ElemEllipse::new(
+            coord! {x: 100., y: 100.},
+            60.,
+            40.,
+        )));
+        let corner = coord! {x: 41., y: 61.};
+        scene.pointer_move(corner);
+        assert_eq!(scene.hover(), None);
+
+        scene.select(id, false);
+        scene.pointer_move(corner);
+        assert_eq!(scene.hover(), Some(id));
+        drag(&mut scene, corner, coord! {x: 0., y: 0.});
+        let bounds = scene.bounds(id).unwrap();
+        assert_eq!(bounds.min(), coord! {x: 0., y: 0.});
+        assert_eq!(bounds.max(), coord! {x: 160., y: 140.});
+    }
+
+    #[test]
+    fn undoes_a_midpoint_drag_with_its_new_vertex() {
+        let mut scene = Scene::new();
+        let points = vec![coord! {x: 10., y: 10.}, coord! {x: 110., y: 10.}];
+        let id = scene.add(Box::new(ElemPolyline::new(points.clone(), false)));
+        scene.select(id, false);
+        // vertices, then the midpoint of the one segment
+        assert_eq!(scene.get(id).unwrap().get_vertex().len(), 3);
+        drag(&mut scene, coord! {x: 60., y: 10.}, coord! {x: 60., y: 50.});
+        assert_eq!(
+            scene.get(id).unwrap().to_doc(),
+            ElemDoc::Polyline {
+                points: vec![[10., 10.], [60., 50.], [110., 10.]]
+            }
+        );
+        assert!(scene.undo());
+        assert_eq!(
+            scene.get(id).unwrap().to_doc(),
+            ElemDoc::Polyline {
+                points: vec![[10., 10.], [110., 10.]]
+            }
+        );
+    }
+
+    #[test]
+    fn records_a_click_that_splits_a_segment() {
+        let mut scene = Scene::new();
+        let points = vec![coord! {x: 10., y: 10.}, coord! {x: 110., y: 10.}];
+        let id = scene.add(Box::new(ElemPolyline::new(points, false)));
+        scene.select(id, false);
+        drag(&mut scene, coord! {x: 60., y: 10.}, coord! {x: 60., y: 10.});
+        assert_eq!(scene.get(id).unwrap().get_vertex().len(), 5);
+        assert!(scene.undo());
+        assert_eq!(scene.get(id).unwrap().get_vertex().len(), 3);
+    }
+
+    #[test]
+    fn keeps_resizing_the_grabbed_element_when_hover_moves() {
+        let mut scene = Scene::new();
+        let small = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 60., y: 60.},
+        );
+        let big = scene.add(Box::new(ElemRect::new(coord! {x: 100., y: 100.}, 80., 80.)));
+        scene.select(small, false);
+        scene.pointer_move(coord! {x: 60., y: 60.});
+        scene.pointer_press(coord! {x: 60., y: 60.});
+        scene.pointer_drag(coord! {x: 40., y: 40.});
+        // the pointer is over the other rect now
+        scene.pointer_move(coord! {x: 120., y: 120.});
+        assert_eq!(scene.hover(), Some(big));
+        scene.pointer_drag(coord! {x: 120., y: 120.});
+        scene.pointer_release();
+        assert_eq!(
+            scene.bounds(small).unwrap().max(),
+            coord! {x: 120., y: 120.}
+        );
+        assert_eq!(scene.bounds(big).unwrap().min(), coord! {x: 100., y: 100.});
+        assert!(scene.undo());
+        assert_eq!(scene.bounds(small).unwrap().max(), coord! {x: 60., y: 60.});
+    }
+
+    #[test]
+    fn records_picture_moves_but_not_clicks() {
+        let mut scene = Scene::new();
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(40, 30)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let picture = ElemImage::embedded(coord! {x: 10., y: 10.}, png.get_ref()).unwrap();
+        let id = scene.add(Box::new(picture));
+        drag(&mut scene, coord! {x: 20., y: 20.}, coord! {x: 20., y: 20.});
+        assert_eq!(scene.history().len(), 1);
+        drag(&mut scene, coord! {x: 20., y: 20.}, coord! {x: 30., y: 25.});
+        assert_eq!(scene.history().len(), 2);
+        assert_eq!(scene.bounds(id).unwrap().min(), coord! {x: 20., y: 15.});
+    }
+
+    #[test]
+    fn grabs_handles_only_when_selected() {
+        let mut scene = Scene::new();
+        let id = create(
+            &mut scene,
+            new_rect(),
+            coord! {x: 10., y: 10.},
+            coord! {x: 60., y: 60.},
+        );
+        scene.clear_selection();
+        // the first press selects, so the drag moves instead of resizing
+        drag(&mut scene, coord! {x: 60., y: 60.}, coord! {x: 70., y: 70.});
+        let bounds = scene.bounds(id).unwrap();
+        assert_eq!(bounds.min(), coord! {x: 20., y: 20.});
+        assert_eq!(bounds.max(), coord! {x: 70., y: 70.});
+    }
+}
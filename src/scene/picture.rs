@@ -0,0 +1,229 @@
+use std::{fs, path::Path, rc::Rc};
+
+use geo::{coord, Coordinate, Point};
+use image::{ImageFormat, ImageResult, RgbaImage};
+
+use super::{begin_stroke, Elem, ElemDoc, ElemRect, Highlight, Painter, Style};
+
+// A raster picture. The pixels are decoded once and shared between clones,
+// `src` is what gets saved: a data URI with the original file bytes, or the
+// path of the file.
+#[derive(Clone)]
+pub struct ElemImage {
+    pub bounds: ElemRect,
+    pub opacity: f64,
+    src: Rc<String>,
+    // None when `src` could not be read, a placeholder is drawn instead
+    pixels: Option<Rc<RgbaImage>>,
+    constrained: bool,
+}
+
+fn mime_type(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::Gif => Some("image/gif"),
+        _ => None,
+    }
+}
+
+// the file bytes as a data URI, other formats than PNG/JPEG/GIF are
+// converted to PNG first so every SVG viewer can show them
+fn data_uri(bytes: &[u8], pixels: &RgbaImage) -> ImageResult<String> {
+    let format = image::guess_format(bytes)?;
+    let uri = match mime_type(format) {
+        Some(mime) => format!("data:{};base64,{}", mime, base64::encode(bytes)),
+        None => {
+            let mut png = std::io::Cursor::new(Vec::new());
+            pixels.write_to(&mut png, ImageFormat::Png)?;
+            format!("data:image/png;base64,{}", base64::encode(png.into_inner()))
+        }
+    };
+    Ok(uri)
+}
+
+// the pixels behind a saved `src`
+fn decode_src(src: &str) -> Option<RgbaImage> {
+    let bytes = match src.strip_prefix("data:") {
+        Some(uri) => base64::decode(uri.split_once(";base64,")?.1).ok()?,
+        None => fs::read(src).ok()?,
+    };
+    image::load_from_memory(&bytes)
+        .ok()
+        .map(|img| img.to_rgba8())
+}
+
+impl ElemImage {
+    fn with_pixels(tl_coord: Coordinate, src: String, pixels: RgbaImage) -> Self {
+        let (w, h) = pixels.dimensions();
+        ElemImage {
+            bounds: ElemRect::new(tl_coord, w as f64, h as f64),
+            opacity: 1.,
+            src: Rc::new(src),
+            pixels: Some(Rc::new(pixels)),
+            constrained: false,
+        }
+    }
+
+    // decoded file bytes kept inside the document
+    pub fn embedded(tl_coord: Coordinate, bytes: &[u8]) -> ImageResult<Self> {
+        let pixels = image::load_from_memory(bytes)?.to_rgba8();
+        let src = data_uri(bytes, &pixels)?;
+        Ok(ElemImage::with_pixels(tl_coord, src, pixels))
+    }
+
+    // the document only keeps the path
+    pub fn linked(tl_coord: Coordinate, path: &Path) -> ImageResult<Self> {
+        let pixels = image::open(path)?.to_rgba8();
+        let src = path.to_string_lossy().into_owned();
+        Ok(ElemImage::with_pixels(tl_coord, src, pixels))
+    }
+
+    // as saved in a document; a src that can't be read still loads
+    pub fn from_src(bounds: ElemRect, opacity: f64, src: String) -> Self {
+        let pixels = decode_src(&src).map(Rc::new);
+        ElemImage {
+            bounds,
+            opacity,
+            src: Rc::new(src),
+            pixels,
+            constrained: false,
+        }
+    }
+
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        self.src.starts_with("data:")
+    }
+
+    pub fn pixels(&self) -> Option<&RgbaImage> {
+        self.pixels.as_deref()
+    }
+
+    // same box and opacity over the very same picture
+    pub fn same_picture(&self, other: &ElemImage) -> bool {
+        let same_pixels = match (&self.pixels, &other.pixels) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.bounds == other.bounds
+            && self.opacity == other.opacity
+            && Rc::ptr_eq(&self.src, &other.src)
+            && same_pixels
+    }
+
+    // width over height of the pixels, or of the box without them
+    pub fn aspect(&self) -> f64 {
+        match self.pixels() {
+            Some(pixels) if pixels.height() > 0 => pixels.width() as f64 / pixels.height() as f64,
+            _ if self.bounds.height > 0. => self.bounds.width / self.bounds.height,
+            _ => 1.,
+        }
+    }
+
+    // the largest size inside `width` x `height` with the same aspect ratio
+    pub fn fit(&mut self, width: f64, height: f64) {
+        let scale = (width / self.bounds.width).min(height / self.bounds.height);
+        self.bounds.width *= scale;
+        self.bounds.height *= scale;
+    }
+}
+
+impl Elem for ElemImage {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        let vertex = self.get_vertex();
+        let tl = vertex[0];
+        let (w, h) = (self.bounds.width.abs(), self.bounds.height.abs());
+        if self.pixels.is_some() {
+            painter.image(self);
+            begin_stroke(painter, &vertex, &Style::default(), highlight);
+            if highlight != Highlight::None {
+                painter.rect(tl, w, h);
+            }
+        } else {
+            // a crossed box where the picture is missing
+            begin_stroke(painter, &vertex, &Style::default(), highlight);
+            painter.rect(tl, w, h);
+            painter.line(vertex[0], vertex[2]);
+            painter.line(vertex[1], vertex[3]);
+        }
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        self.bounds.get_vertex()
+    }
+
+    fn creating(&mut self, _from_coord: Coordinate, end_coord: Coordinate) {
+        self.bounds.tl_coord = end_coord;
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.bounds.edit_moving(from_coord, end_coord);
+    }
+
+    // corners keep the aspect ratio unless Shift is held, the opposite
+    // corner stays put
+    fn edit_resizing(&mut self, from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        if self.constrained {
+            self.bounds
+                .edit_resizing(from_coord, end_coord, drag_vertex);
+            return;
+        }
+        if !(0..4).contains(&drag_vertex) {
+            return;
+        }
+        let vertex = self.get_vertex();
+        let anchor = vertex[((drag_vertex + 2) % 4) as usize];
+        let aspect = self.aspect();
+        let w = (end_coord.x - anchor.x)
+            .abs()
+            .max((end_coord.y - anchor.y).abs() * aspect)
+            .max(1.);
+        let h = w / aspect;
+        self.bounds.width = w;
+        self.bounds.height = h;
+        self.bounds.tl_coord = coord! {
+            x: if drag_vertex == 0 || drag_vertex == 3 { anchor.x - w } else { anchor.x },
+            y: if drag_vertex <= 1 { anchor.y - h } else { anchor.y },
+        };
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        self.bounds.scale(origin, sx, sy);
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        self.bounds.hover_condition(mouse_point)
+    }
+
+    fn set_constrained(&mut self, constrained: bool) {
+        self.constrained = constrained;
+    }
+
+    fn image(&self) -> Option<&ElemImage> {
+        Some(self)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(self.clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        "image"
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Image {
+            x: self.bounds.tl_coord.x,
+            y: self.bounds.tl_coord.y,
+            width: self.bounds.width,
+            height: self.bounds.height,
+            opacity: self.opacity,
+            src: String::clone(&self.src),
+        }
+    }
+}
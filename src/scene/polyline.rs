@@ -0,0 +1,212 @@
+use std::mem::size_of;
+
+use geo::{coord, Contains, Coordinate, EuclideanDistance, LineString, Point, Polygon};
+
+use super::{
+    begin_stroke, document::to_xy, scale_coord, Elem, ElemDoc, Highlight, Painter, Style,
+    GRAB_DISTANCE,
+};
+
+// Connected segments placed one click at a time. A closed one is a polygon.
+// Handles after the vertices sit on segment midpoints; grabbing one inserts
+// a vertex there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElemPolyline {
+    pub points: Vec<Coordinate>,
+    pub closed: bool,
+    pub style: Style,
+    // rubber band to the pointer while placing
+    preview: Option<Coordinate>,
+    placing: bool,
+}
+
+impl ElemPolyline {
+    pub fn new(points: Vec<Coordinate>, closed: bool) -> Self {
+        ElemPolyline {
+            points,
+            closed,
+            style: Style::default(),
+            preview: None,
+            placing: false,
+        }
+    }
+
+    // an empty shape that is built by clicks
+    pub fn placed(closed: bool) -> Self {
+        ElemPolyline {
+            placing: true,
+            ..ElemPolyline::new(Vec::new(), closed)
+        }
+    }
+
+    fn min_points(&self) -> usize {
+        if self.closed {
+            3
+        } else {
+            2
+        }
+    }
+
+    fn midpoints(&self) -> Vec<Coordinate> {
+        let n = self.points.len();
+        let segments = if self.closed && n > 2 {
+            n
+        } else {
+            n.saturating_sub(1)
+        };
+        (0..segments)
+            .map(|i| {
+                let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+                coord! {x: (a.x + b.x) / 2., y: (a.y + b.y) / 2.}
+            })
+            .collect()
+    }
+}
+
+impl Elem for ElemPolyline {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        begin_stroke(painter, &self.get_vertex(), &self.style, highlight);
+        if self.placing {
+            let mut points = self.points.clone();
+            points.extend(self.preview);
+            painter.polyline(&points, false);
+        } else {
+            painter.polyline(&self.points, self.closed);
+        }
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        let mut vertex = self.points.clone();
+        if !self.placing {
+            vertex.extend(self.midpoints());
+        }
+        vertex
+    }
+
+    // shapes are placed by clicks, a drag only moves the last vertex
+    fn creating(&mut self, _from_coord: Coordinate, end_coord: Coordinate) {
+        if let Some(last) = self.points.last_mut() {
+            *last = end_coord;
+        }
+    }
+
+    fn placed_by_clicks(&self) -> bool {
+        self.placing
+    }
+
+    fn place_vertex(&mut self, coord: Coordinate) {
+        self.points.push(coord);
+    }
+
+    fn preview_vertex(&mut self, coord: Option<Coordinate>) {
+        self.preview = coord;
+    }
+
+    fn finish_creating(&mut self) {
+        self.placing = false;
+        self.preview = None;
+        // a double click lands twice on the same spot
+        self.points.dedup();
+        if self.closed && self.points.len() > 2 && self.points.first() == self.points.last() {
+            self.points.pop();
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.points.len() >= self.min_points()
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let x_dif = end_coord.x - from_coord.x;
+        let y_dif = end_coord.y - from_coord.y;
+        for p in &mut self.points {
+            p.x += x_dif;
+            p.y += y_dif;
+        }
+    }
+
+    fn grab_vertex(&mut self, index: i32) -> i32 {
+        let n = self.points.len() as i32;
+        if index < n {
+            return index;
+        }
+        // a midpoint handle: split its segment
+        let segment = (index - n) as usize;
+        let mid = self.midpoints()[segment];
+        self.points.insert(segment + 1, mid);
+        segment as i32 + 1
+    }
+
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        if let Some(p) = self.points.get_mut(drag_vertex as usize) {
+            *p = end_coord;
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        for p in &mut self.points {
+            *p = scale_coord(*p, origin, sx, sy);
+        }
+    }
+
+    fn remove_vertex(&mut self, index: i32) -> bool {
+        let index = index as usize;
+        if index >= self.points.len() || self.points.len() <= self.min_points() {
+            return false;
+        }
+        self.points.remove(index);
+        true
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        if self.points.is_empty() {
+            return false;
+        }
+        let near_vertex = self
+            .get_vertex()
+            .iter()
+            .any(|v| Point::from(*v).euclidean_distance(&mouse_point) < GRAB_DISTANCE);
+        let outline = LineString::from(self.points.clone());
+        if self.closed && self.points.len() > 2 {
+            let polygon = Polygon::new(outline, vec![]);
+            near_vertex
+                || polygon.contains(&mouse_point)
+                || mouse_point.euclidean_distance(polygon.exterior()) < GRAB_DISTANCE
+        } else {
+            near_vertex || mouse_point.euclidean_distance(&outline) < GRAB_DISTANCE
+        }
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(self.clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        if self.closed {
+            "polygon"
+        } else {
+            "polyline"
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        self.points.len() * size_of::<Coordinate>()
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        let points = self.points.iter().map(|p| to_xy(*p)).collect();
+        if self.closed {
+            ElemDoc::Polygon { points }
+        } else {
+            ElemDoc::Polyline { points }
+        }
+    }
+}
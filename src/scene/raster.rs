@@ -0,0 +1,479 @@
+// Software rendering of a scene onto an `RgbaImage`, for exporting without
+// a display. Strokes are anti-aliased by their distance to the outline.
+
+use geo::{Contains, Coordinate, EuclideanDistance, Line, LineString, Point, Polygon};
+use image::{imageops, Rgba, RgbaImage};
+
+use super::{
+    bezier::flatten, text::measure_line, Cap, Color, ElemImage, ElemText, Highlight, Painter,
+    Scene, Style,
+};
+use crate::watermark;
+
+// segments used to flatten a curve
+const QUAD_STEPS: usize = 32;
+
+pub struct RasterPainter {
+    pub img: RgbaImage,
+    scale: f64,
+    style: Style,
+}
+
+impl RasterPainter {
+    // `scale` maps canvas units to pixels
+    pub fn new(width: u32, height: u32, scale: f64, background: Option<Color>) -> Self {
+        let fill = background.unwrap_or([0, 0, 0, 0]);
+        RasterPainter {
+            img: RgbaImage::from_pixel(width, height, Rgba(fill)),
+            scale,
+            style: Style::solid([0, 0, 0, 0xff], 1.),
+        }
+    }
+
+    fn px(&self, c: Coordinate) -> Coordinate {
+        Coordinate {
+            x: c.x * self.scale,
+            y: c.y * self.scale,
+        }
+    }
+
+    // blends `color` into the pixel with `coverage` in 0..=1
+    fn blend(&mut self, x: u32, y: u32, color: Color, coverage: f64) {
+        let a = color[3] as f64 / 255. * coverage;
+        let dst = self.img.get_pixel_mut(x, y);
+        let dst_a = dst[3] as f64 / 255.;
+        let out_a = a + dst_a * (1. - a);
+        if out_a <= 0. {
+            return;
+        }
+        for i in 0..3 {
+            let c = (color[i] as f64 * a + dst[i] as f64 * dst_a * (1. - a)) / out_a;
+            dst[i] = c.round() as u8;
+        }
+        dst[3] = (out_a * 255.).round() as u8;
+    }
+
+    // strokes connected segments given in canvas units, dashed and capped
+    // as the style says; joins are always round. Every pixel is blended once
+    // with its best coverage so joins don't darken.
+    pub fn stroke_polyline(&mut self, points: &[Coordinate], closed: bool) {
+        if points.is_empty() {
+            return;
+        }
+        let mut points: Vec<Coordinate> = points.iter().map(|p| self.px(*p)).collect();
+        if closed && points.len() > 2 {
+            points.push(points[0]);
+        }
+        let width = self.style.width * self.scale;
+        let pattern: Vec<f64> = self
+            .style
+            .dash
+            .pattern(self.style.width)
+            .iter()
+            .map(|d| d * self.scale)
+            .collect();
+        let runs = if pattern.is_empty() {
+            vec![points.clone()]
+        } else {
+            dash_runs(&points, &pattern)
+        };
+        // a closed solid outline has no ends to cap
+        let capped = !(closed && pattern.is_empty() && points.len() > 3);
+
+        let half = width.max(1.) / 2.;
+        let margin = half * 1.5 + 1.;
+        let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) - margin;
+        let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - margin;
+        let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) + margin;
+        let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) + margin;
+        let (w, h) = self.img.dimensions();
+        let x0 = min_x.floor().max(0.) as u32;
+        let y0 = min_y.floor().max(0.) as u32;
+        let x1 = (max_x.ceil().max(0.) as u32).min(w);
+        let y1 = (max_y.ceil().max(0.) as u32).min(h);
+
+        let color = self.style.stroke_color();
+        let cap = if capped { Some(self.style.cap) } else { None };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let center = Coordinate {
+                    x: x as f64 + 0.5,
+                    y: y as f64 + 0.5,
+                };
+                let d = runs
+                    .iter()
+                    .map(|run| run_distance(center, run, cap, half))
+                    .fold(f64::INFINITY, f64::min);
+                let coverage = (half + 0.5 - d).clamp(0., 1.);
+                if coverage > 0. {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+}
+
+// the drawn stretches of a polyline under a dash pattern of lengths in
+// pixels, alternating on and off
+fn dash_runs(points: &[Coordinate], pattern: &[f64]) -> Vec<Vec<Coordinate>> {
+    let mut runs = Vec::new();
+    let mut run = vec![points[0]];
+    let (mut index, mut left) = (0, pattern[0]);
+    for w in points.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let len = Point::from(a).euclidean_distance(&Point::from(b));
+        let mut done = 0.;
+        while len - done > left {
+            done += left;
+            let t = done / len;
+            let p = Coordinate {
+                x: a.x + (b.x - a.x) * t,
+                y: a.y + (b.y - a.y) * t,
+            };
+            run.push(p);
+            if index % 2 == 0 {
+                runs.push(std::mem::take(&mut run));
+            }
+            index = (index + 1) % pattern.len();
+            left = pattern[index];
+        }
+        left -= len - done;
+        if index % 2 == 0 {
+            run.push(b);
+        }
+    }
+    if index % 2 == 0 && run.len() > 1 {
+        runs.push(run);
+    }
+    runs
+}
+
+// distance from `p` to a stroked run, with the ends shaped by `cap`; the
+// stroke covers what is within half its width
+fn run_distance(p: Coordinate, run: &[Coordinate], cap: Option<Cap>, half: f64) -> f64 {
+    if run.len() == 1 {
+        return match cap {
+            Some(Cap::Butt) => f64::INFINITY,
+            _ => Point::from(p).euclidean_distance(&Point::from(run[0])),
+        };
+    }
+    let last = run.len() - 2;
+    let mut best = f64::INFINITY;
+    for (i, w) in run.windows(2).enumerate() {
+        let (a, b) = (w[0], w[1]);
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let len = (dx * dx + dy * dy).sqrt();
+        let d = if len == 0. {
+            Point::from(p).euclidean_distance(&Point::from(a))
+        } else {
+            let (ux, uy) = (dx / len, dy / len);
+            let along = (p.x - a.x) * ux + (p.y - a.y) * uy;
+            let perp = ((p.x - a.x) * uy - (p.y - a.y) * ux).abs();
+            // how far past an end of the run
+            let beyond = if i == 0 && along < 0. {
+                -along
+            } else if i == last && along > len {
+                along - len
+            } else {
+                0.
+            };
+            match cap {
+                Some(Cap::Butt) if beyond > 0. => perp.max(half + beyond),
+                Some(Cap::Square) if beyond > 0. => perp.max(beyond),
+                _ => Point::from(p).euclidean_distance(&Line::new(a, b)),
+            }
+        };
+        best = best.min(d);
+    }
+    best
+}
+
+impl RasterPainter {
+    // strokes an outline in canvas units, filled first when it is closed
+    // and the style has a fill
+    fn shape(&mut self, points: &[Coordinate], closed: bool) {
+        if closed {
+            if let Some(fill) = self.style.fill_color() {
+                self.fill_points(points, fill);
+            }
+        }
+        if self.style.stroke_color()[3] > 0 {
+            self.stroke_polyline(points, closed);
+        }
+    }
+
+    // fills a closed outline given in canvas units, edges are anti-aliased
+    // over one pixel by the distance to the outline
+    pub fn fill_points(&mut self, points: &[Coordinate], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let points: Vec<Coordinate> = points.iter().map(|p| self.px(*p)).collect();
+        let polygon = Polygon::new(LineString::from(points.clone()), vec![]);
+        let outline = polygon.exterior();
+
+        let (w, h) = self.img.dimensions();
+        let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) - 1.;
+        let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - 1.;
+        let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) + 1.;
+        let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) + 1.;
+        let x0 = min_x.floor().max(0.) as u32;
+        let y0 = min_y.floor().max(0.) as u32;
+        let x1 = (max_x.ceil().max(0.) as u32).min(w);
+        let y1 = (max_y.ceil().max(0.) as u32).min(h);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                let d = center.euclidean_distance(outline);
+                let coverage = if polygon.contains(&center) {
+                    (0.5 + d).min(1.)
+                } else {
+                    (0.5 - d).max(0.)
+                };
+                if coverage > 0. {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+}
+
+impl Painter for RasterPainter {
+    fn set_style(&mut self, style: &Style) {
+        self.style = *style;
+    }
+
+    fn line(&mut self, from: Coordinate, to: Coordinate) {
+        self.stroke_polyline(&[from, to], false);
+    }
+
+    fn rect(&mut self, tl: Coordinate, width: f64, height: f64) {
+        let corners = [
+            tl,
+            Coordinate {
+                x: tl.x + width,
+                y: tl.y,
+            },
+            Coordinate {
+                x: tl.x + width,
+                y: tl.y + height,
+            },
+            Coordinate {
+                x: tl.x,
+                y: tl.y + height,
+            },
+        ];
+        self.shape(&corners, true);
+    }
+
+    fn ellipse(&mut self, center: Coordinate, rx: f64, ry: f64) {
+        // enough segments that each one spans about two pixels
+        let r = rx.max(ry) * self.scale;
+        let steps = ((r * std::f64::consts::PI).ceil() as usize).clamp(16, 1024);
+        let points: Vec<Coordinate> = (0..steps)
+            .map(|i| {
+                let a = i as f64 / steps as f64 * std::f64::consts::TAU;
+                Coordinate {
+                    x: center.x + rx * a.cos(),
+                    y: center.y + ry * a.sin(),
+                }
+            })
+            .collect();
+        self.shape(&points, true);
+    }
+
+    fn polyline(&mut self, points: &[Coordinate], closed: bool) {
+        self.shape(points, closed);
+    }
+
+    fn quad(&mut self, from: Coordinate, control: Coordinate, to: Coordinate) {
+        let points: Vec<Coordinate> = (0..=QUAD_STEPS)
+            .map(|i| {
+                let t = i as f64 / QUAD_STEPS as f64;
+                let u = 1. - t;
+                Coordinate {
+                    x: u * u * from.x + 2. * u * t * control.x + t * t * to.x,
+                    y: u * u * from.y + 2. * u * t * control.y + t * t * to.y,
+                }
+            })
+            .collect();
+        self.stroke_polyline(&points, false);
+    }
+
+    fn bezier(&mut self, points: &[Coordinate], closed: bool) {
+        self.shape(&flatten(points, QUAD_STEPS), closed);
+    }
+
+    fn fill_polygon(&mut self, points: &[Coordinate]) {
+        self.fill_points(points, self.style.stroke_color());
+    }
+
+    fn text(&mut self, text: &ElemText) {
+        let style = &text.style;
+        let (width, _) = text.size();
+        let (iw, ih) = self.img.dimensions();
+        for (i, line) in text.lines().iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let offset = style.align.offset(measure_line(line, style.size), width);
+            let x = (text.origin.x + offset) * self.scale;
+            let y = (text.origin.y + i as f64 * text.line_height()) * self.scale;
+            // the stamp has a one pixel margin
+            let stamp = watermark::text_stamp(line, (style.size * self.scale) as f32, style.color);
+            let (left, top) = (x.round() as i64 - 1, y.round() as i64 - 1);
+            for (sx, sy, px) in stamp.enumerate_pixels() {
+                let (px_x, px_y) = (left + sx as i64, top + sy as i64);
+                if px_x < 0 || px_y < 0 || px_x >= iw as i64 || px_y >= ih as i64 || px[3] == 0 {
+                    continue;
+                }
+                let color = [px[0], px[1], px[2], 0xff];
+                self.blend(px_x as u32, px_y as u32, color, px[3] as f64 / 255.);
+            }
+        }
+    }
+
+    // resampled to the target size, then blended with the opacity
+    fn image(&mut self, image: &ElemImage) {
+        let pixels = match image.pixels() {
+            Some(pixels) => pixels,
+            None => return,
+        };
+        let b = &image.bounds;
+        let tl = self.px(b.tl_coord);
+        let w = (b.width * self.scale).round() as u32;
+        let h = (b.height * self.scale).round() as u32;
+        if w == 0 || h == 0 {
+            return;
+        }
+        let scaled = imageops::resize(pixels, w, h, imageops::FilterType::Triangle);
+        let (iw, ih) = self.img.dimensions();
+        let (left, top) = (tl.x.round() as i64, tl.y.round() as i64);
+        for (sx, sy, px) in scaled.enumerate_pixels() {
+            let (px_x, px_y) = (left + sx as i64, top + sy as i64);
+            if px_x < 0 || px_y < 0 || px_x >= iw as i64 || px_y >= ih as i64 {
+                continue;
+            }
+            let coverage = px[3] as f64 / 255. * image.opacity;
+            if coverage > 0. {
+                let color = [px[0], px[1], px[2], 0xff];
+                self.blend(px_x as u32, px_y as u32, color, coverage);
+            }
+        }
+    }
+
+    // grips are an editing aid, not part of the drawing
+    fn handle(&mut self, _center: Coordinate, _color: Color) {}
+}
+
+// the whole canvas at `scale` pixels per canvas unit
+pub fn rasterize(scene: &Scene, scale: f64, background: Option<Color>) -> RgbaImage {
+    let (w, h) = scene.canvas_size();
+    let width = (w as f64 * scale).round().max(1.) as u32;
+    let height = (h as f64 * scale).round().max(1.) as u32;
+    let mut painter = RasterPainter::new(width, height, scale, background);
+    for (_, elem) in scene.iter() {
+        elem.draw(&mut painter, Highlight::None);
+    }
+    painter.img
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::coord;
+
+    use super::*;
+    use crate::{
+        golden,
+        scene::{
+            Cap, Dash, ElemArrow, ElemBezier, ElemEllipse, ElemLine, ElemPolyline, ElemRect,
+            ElemText, Head, Node, Style, TextStyle,
+        },
+    };
+
+    const WHITE: Color = [0xff, 0xff, 0xff, 0xff];
+
+    fn shapes() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_canvas_size(120, 80);
+        let mut rect = ElemRect::new(coord! {x: 10., y: 10.}, 40., 25.);
+        rect.style = Style {
+            fill: Some([0x30, 0x90, 0x30, 0xff]),
+            ..Style::solid([0, 0, 0, 0xff], 2.)
+        };
+        let mut ellipse = ElemEllipse::new(coord! {x: 85., y: 25.}, 25., 15.);
+        ellipse.bounds.style = Style {
+            fill: Some([0, 0, 0xff, 0x80]),
+            ..Style::default()
+        };
+        let mut line = ElemLine::new(coord! {x: 10., y: 50.}, coord! {x: 110., y: 50.});
+        line.style = Style {
+            dash: Dash::Dash,
+            cap: Cap::Round,
+            ..Style::solid([0x80, 0, 0x80, 0xff], 4.)
+        };
+        let polyline = ElemPolyline::new(
+            vec![
+                coord! {x: 10., y: 75.},
+                coord! {x: 30., y: 60.},
+                coord! {x: 50., y: 75.},
+            ],
+            false,
+        );
+        let arrow = ElemArrow::new(coord! {x: 60., y: 75.}, coord! {x: 110., y: 60.})
+            .with_heads(Head::None, Head::Triangle);
+        scene.push(Box::new(rect));
+        scene.push(Box::new(ellipse));
+        scene.push(Box::new(line));
+        scene.push(Box::new(polyline));
+        scene.push(Box::new(arrow));
+        scene
+    }
+
+    #[test]
+    fn shapes_match_golden() {
+        let img = rasterize(&shapes(), 1., Some(WHITE));
+        assert_eq!(img.dimensions(), (120, 80));
+        // the fill inside the rect and the background outside everything
+        assert_eq!(img.get_pixel(30, 22).0, [0x30, 0x90, 0x30, 0xff]);
+        assert_eq!(img.get_pixel(2, 2).0, WHITE);
+        golden::check("raster_shapes", &img);
+    }
+
+    #[test]
+    fn scales_the_canvas() {
+        let img = rasterize(&shapes(), 2., Some(WHITE));
+        assert_eq!(img.dimensions(), (240, 160));
+        assert_eq!(img.get_pixel(60, 44).0, [0x30, 0x90, 0x30, 0xff]);
+        golden::check("raster_shapes_2x", &img);
+    }
+
+    #[test]
+    fn curves_and_text_match_golden() {
+        let mut scene = Scene::new();
+        scene.set_canvas_size(120, 80);
+        let mut start = Node::corner(coord! {x: 10., y: 70.});
+        start.out_ctrl = coord! {x: 30., y: 10.};
+        let mut end = Node::corner(coord! {x: 110., y: 70.});
+        end.in_ctrl = coord! {x: 90., y: 10.};
+        scene.push(Box::new(ElemBezier::new(vec![start, end], false)));
+        scene.push(Box::new(ElemText::new(
+            coord! {x: 30., y: 45.},
+            "Hi\nthere",
+            TextStyle {
+                size: 14.,
+                ..TextStyle::default()
+            },
+        )));
+        golden::check("raster_curves_text", &rasterize(&scene, 1., Some(WHITE)));
+    }
+
+    #[test]
+    fn leaves_the_background_transparent() {
+        let img = rasterize(&shapes(), 1., None);
+        assert_eq!(img.get_pixel(2, 2).0[3], 0);
+        assert_eq!(img.get_pixel(30, 22).0, [0x30, 0x90, 0x30, 0xff]);
+        golden::check("raster_shapes_transparent", &img);
+    }
+}
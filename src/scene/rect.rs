@@ -0,0 +1,144 @@
+use geo::{coord, point, Coordinate, EuclideanDistance, Intersects, Point, Rect};
+
+use super::{begin_stroke, scale_coord, Elem, ElemDoc, Highlight, Painter, Style, GRAB_DISTANCE};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElemRect {
+    pub tl_coord: Coordinate, // top left coord
+    pub width: f64,
+    pub height: f64,
+    pub style: Style,
+}
+
+impl ElemRect {
+    pub fn new(tl_coord: Coordinate, width: f64, height: f64) -> Self {
+        ElemRect {
+            tl_coord,
+            width,
+            height,
+            style: Style::default(),
+        }
+    }
+}
+
+impl Elem for ElemRect {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        let vec = self.get_vertex();
+        begin_stroke(painter, &vec, &self.style, highlight);
+        painter.rect(vec[0], self.width.abs(), self.height.abs());
+    }
+
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        let mut tl = coord! {x: self.tl_coord.x, y: self.tl_coord.y};
+        let mut br = coord! {x: self.tl_coord.x + self.width, y: self.tl_coord.y + self.height};
+
+        if tl.x > br.x {
+            std::mem::swap(&mut tl.x, &mut br.x);
+        }
+        if tl.y > br.y {
+            std::mem::swap(&mut tl.y, &mut br.y);
+        }
+
+        let bl = coord! {x: tl.x, y: br.y};
+        let tr = coord! {x: br.x, y: tl.y};
+
+        Vec::from([tl, tr, br, bl])
+    }
+
+    fn creating(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let mut tfrom = from_coord;
+        let mut tend = end_coord;
+
+        if tfrom.x > tend.x {
+            std::mem::swap(&mut tfrom.x, &mut tend.x);
+        }
+
+        if tfrom.y > tend.y {
+            std::mem::swap(&mut tfrom.y, &mut tend.y);
+        }
+
+        self.tl_coord = tfrom;
+        self.width = tend.x - tfrom.x;
+        self.height = tend.y - tfrom.y;
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        let x_dif = end_coord.x - from_coord.x;
+        let y_dif = end_coord.y - from_coord.y;
+
+        self.tl_coord.x += x_dif;
+        self.tl_coord.y += y_dif;
+    }
+
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        match drag_vertex {
+            0 => {
+                self.width += self.tl_coord.x - end_coord.x;
+                self.height += self.tl_coord.y - end_coord.y;
+                self.tl_coord = end_coord;
+            }
+            1 => {
+                self.width = end_coord.x - self.tl_coord.x;
+                self.height += self.tl_coord.y - end_coord.y;
+                self.tl_coord.y = end_coord.y;
+            }
+            2 => {
+                self.width = end_coord.x - self.tl_coord.x;
+                self.height = end_coord.y - self.tl_coord.y;
+            }
+            3 => {
+                self.height = end_coord.y - self.tl_coord.y;
+                self.width += self.tl_coord.x - end_coord.x;
+                self.tl_coord.x = end_coord.x;
+            }
+            _ => (),
+        }
+        if self.width < 0. {
+            self.width = 0.;
+            self.tl_coord.x = end_coord.x;
+        }
+        if self.height < 0. {
+            self.height = 0.;
+            self.tl_coord.y = end_coord.y;
+        }
+    }
+
+    fn scale(&mut self, origin: Coordinate, sx: f64, sy: f64) {
+        self.tl_coord = scale_coord(self.tl_coord, origin, sx, sy);
+        self.width *= sx;
+        self.height *= sy;
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        let vertex = self.get_vertex();
+        Rect::new(vertex[0], vertex[2]).intersects(&mouse_point)
+            || vertex
+                .iter()
+                .any(|v| point! {*v}.euclidean_distance(&mouse_point) < GRAB_DISTANCE)
+    }
+
+    fn style(&self) -> Option<&Style> {
+        Some(&self.style)
+    }
+
+    fn style_mut(&mut self) -> Option<&mut Style> {
+        Some(&mut self.style)
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(*self)
+    }
+
+    fn kind(&self) -> &'static str {
+        "rect"
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Rect {
+            x: self.tl_coord.x,
+            y: self.tl_coord.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
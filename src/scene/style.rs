@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Highlight, RED};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dash {
+    Solid,
+    Dash,
+    Dot,
+    DashDot,
+}
+
+impl Dash {
+    pub const ALL: [Dash; 4] = [Dash::Solid, Dash::Dash, Dash::Dot, Dash::DashDot];
+
+    // on and off lengths for a stroke of `width`, empty when solid
+    pub fn pattern(&self, width: f64) -> Vec<f64> {
+        let w = width.max(1.);
+        match self {
+            Dash::Solid => Vec::new(),
+            Dash::Dash => vec![4. * w, 2. * w],
+            Dash::Dot => vec![w, 2. * w],
+            Dash::DashDot => vec![4. * w, 2. * w, w, 2. * w],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cap {
+    Butt,
+    Round,
+    Square,
+}
+
+impl Cap {
+    pub const ALL: [Cap; 3] = [Cap::Butt, Cap::Round, Cap::Square];
+
+    // the SVG keyword
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cap::Butt => "butt",
+            Cap::Round => "round",
+            Cap::Square => "square",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Join {
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl Join {
+    pub const ALL: [Join; 3] = [Join::Miter, Join::Round, Join::Bevel];
+
+    // the SVG keyword
+    pub fn name(&self) -> &'static str {
+        match self {
+            Join::Miter => "miter",
+            Join::Round => "round",
+            Join::Bevel => "bevel",
+        }
+    }
+}
+
+// How a shape is stroked and filled. Missing fields in a document take the
+// defaults, so files that only have a stroke color and width still load.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub stroke: Color,
+    pub width: f64,
+    pub dash: Dash,
+    pub cap: Cap,
+    pub join: Join,
+    // closed shapes only
+    pub fill: Option<Color>,
+    // applies to stroke and fill, on top of their own alpha
+    pub opacity: f64,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            stroke: RED,
+            width: 3.,
+            dash: Dash::Solid,
+            cap: Cap::Butt,
+            join: Join::Miter,
+            fill: None,
+            opacity: 1.,
+        }
+    }
+}
+
+fn with_opacity(color: Color, opacity: f64) -> Color {
+    let [r, g, b, a] = color;
+    [r, g, b, (a as f64 * opacity.clamp(0., 1.)).round() as u8]
+}
+
+impl Style {
+    pub fn solid(stroke: Color, width: f64) -> Self {
+        Style {
+            stroke,
+            width,
+            ..Style::default()
+        }
+    }
+
+    // the colors as painted, with the opacity folded into the alpha
+    pub fn stroke_color(&self) -> Color {
+        with_opacity(self.stroke, self.opacity)
+    }
+
+    pub fn fill_color(&self) -> Option<Color> {
+        self.fill.map(|fill| with_opacity(fill, self.opacity))
+    }
+
+    // selected at half brightness, hovered halfway to white
+    pub fn highlighted(&self, highlight: Highlight) -> Self {
+        let [r, g, b, a] = self.stroke;
+        let stroke = match highlight {
+            Highlight::None => return *self,
+            Highlight::Hover => [r / 2 + 0x80, g / 2 + 0x80, b / 2 + 0x80, a],
+            Highlight::Selected => [r / 2, g / 2, b / 2, a],
+        };
+        Style { stroke, ..*self }
+    }
+}
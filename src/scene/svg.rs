@@ -0,0 +1,869 @@
+// SVG export and import. `SvgPainter` turns the painter calls of every
+// element into SVG shapes, so a new element kind is exported as soon as it
+// can draw. Import goes through usvg, which hands every shape back as a path.
+
+use std::fmt::Write;
+
+use geo::{coord, Coordinate};
+
+use super::{
+    bezier::cubic_point, Align, Cap, Color, Dash, Elem, ElemBezier, ElemEllipse, ElemImage,
+    ElemLine, ElemPolyline, ElemRect, ElemText, FontFamily, Highlight, Join, Node, Painter, Scene,
+    Style, TextStyle,
+};
+
+pub struct SvgPainter {
+    body: String,
+    style: Style,
+}
+
+// `name="#rrggbb"`, with `name-opacity` when not opaque
+fn color_attrs(name: &str, color: Color) -> String {
+    let [r, g, b, a] = color;
+    let mut attrs = format!(r##"{}="#{:02x}{:02x}{:02x}""##, name, r, g, b);
+    if a != 0xff {
+        let _ = write!(attrs, r#" {}-opacity="{}""#, name, a as f64 / 255.);
+    }
+    attrs
+}
+
+impl SvgPainter {
+    pub fn new() -> Self {
+        SvgPainter {
+            body: String::new(),
+            style: Style::default(),
+        }
+    }
+
+    // `filled` for closed shapes, the only ones that take the fill
+    fn stroke_attrs(&self, filled: bool) -> String {
+        let style = &self.style;
+        let mut attrs = match style.fill {
+            Some(fill) if filled => color_attrs("fill", fill),
+            _ => String::from(r#"fill="none""#),
+        };
+        let _ = write!(
+            attrs,
+            r#" {} stroke-width="{}""#,
+            color_attrs("stroke", style.stroke),
+            style.width
+        );
+        let dashes = style.dash.pattern(style.width);
+        if !dashes.is_empty() {
+            let dashes: Vec<String> = dashes.iter().map(|d| d.to_string()).collect();
+            let _ = write!(attrs, r#" stroke-dasharray="{}""#, dashes.join(","));
+        }
+        if style.cap != Cap::Butt {
+            let _ = write!(attrs, r#" stroke-linecap="{}""#, style.cap.name());
+        }
+        if style.join != Join::Miter {
+            let _ = write!(attrs, r#" stroke-linejoin="{}""#, style.join.name());
+        }
+        if style.opacity < 1. {
+            let _ = write!(attrs, r#" opacity="{}""#, style.opacity);
+        }
+        attrs
+    }
+
+    pub fn finish(self, width: u32, height: u32) -> String {
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                "\n{body}</svg>\n"
+            ),
+            w = width,
+            h = height,
+            body = self.body
+        )
+    }
+}
+
+impl Default for SvgPainter {
+    fn default() -> Self {
+        SvgPainter::new()
+    }
+}
+
+impl Painter for SvgPainter {
+    fn set_style(&mut self, style: &Style) {
+        self.style = *style;
+    }
+
+    fn line(&mut self, from: Coordinate, to: Coordinate) {
+        let _ = writeln!(
+            self.body,
+            r#"  <line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#,
+            from.x,
+            from.y,
+            to.x,
+            to.y,
+            self.stroke_attrs(false)
+        );
+    }
+
+    fn rect(&mut self, tl: Coordinate, width: f64, height: f64) {
+        let _ = writeln!(
+            self.body,
+            r#"  <rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+            tl.x,
+            tl.y,
+            width,
+            height,
+            self.stroke_attrs(true)
+        );
+    }
+
+    fn ellipse(&mut self, center: Coordinate, rx: f64, ry: f64) {
+        let _ = writeln!(
+            self.body,
+            r#"  <ellipse cx="{}" cy="{}" rx="{}" ry="{}" {}/>"#,
+            center.x,
+            center.y,
+            rx,
+            ry,
+            self.stroke_attrs(true)
+        );
+    }
+
+    fn polyline(&mut self, points: &[Coordinate], closed: bool) {
+        let _ = writeln!(
+            self.body,
+            r#"  <{} points="{}" {}/>"#,
+            if closed { "polygon" } else { "polyline" },
+            points_attr(points),
+            self.stroke_attrs(closed)
+        );
+    }
+
+    fn quad(&mut self, from: Coordinate, control: Coordinate, to: Coordinate) {
+        let _ = writeln!(
+            self.body,
+            r#"  <path d="M{} {} Q{} {} {} {}" {}/>"#,
+            from.x,
+            from.y,
+            control.x,
+            control.y,
+            to.x,
+            to.y,
+            self.stroke_attrs(false)
+        );
+    }
+
+    fn bezier(&mut self, points: &[Coordinate], closed: bool) {
+        let first = match points.first() {
+            Some(first) => first,
+            None => return,
+        };
+        let mut d = format!("M{} {}", first.x, first.y);
+        for seg in points[1..].chunks_exact(3) {
+            let _ = write!(
+                d,
+                " C{} {} {} {} {} {}",
+                seg[0].x, seg[0].y, seg[1].x, seg[1].y, seg[2].x, seg[2].y
+            );
+        }
+        if closed {
+            d.push_str(" Z");
+        }
+        let _ = writeln!(
+            self.body,
+            r#"  <path d="{}" {}/>"#,
+            d,
+            self.stroke_attrs(closed)
+        );
+    }
+
+    fn fill_polygon(&mut self, points: &[Coordinate]) {
+        let attrs = color_attrs("fill", self.style.stroke_color());
+        let _ = writeln!(
+            self.body,
+            r#"  <polygon points="{}" {}/>"#,
+            points_attr(points),
+            attrs
+        );
+    }
+
+    fn text(&mut self, text: &ElemText) {
+        let style = &text.style;
+        let (width, _) = text.size();
+        let (x, anchor) = match style.align {
+            Align::Left => (text.origin.x, "start"),
+            Align::Center => (text.origin.x + width / 2., "middle"),
+            Align::Right => (text.origin.x + width, "end"),
+        };
+        let attrs = format!(
+            r#"font-family="{}" font-size="{}" text-anchor="{}" {}"#,
+            style.font.css(),
+            style.size,
+            anchor,
+            color_attrs("fill", style.color)
+        );
+        // first baseline at the ascent, roughly 0.8 of the size
+        let _ = write!(
+            self.body,
+            r#"  <text x="{}" y="{}" {}>"#,
+            x,
+            text.origin.y + style.size * 0.8,
+            attrs
+        );
+        for (i, line) in text.lines().iter().enumerate() {
+            let dy = if i == 0 { 0. } else { text.line_height() };
+            let _ = write!(
+                self.body,
+                r#"<tspan x="{}" dy="{}">{}</tspan>"#,
+                x,
+                dy,
+                escape(line)
+            );
+        }
+        let _ = writeln!(self.body, "</text>");
+    }
+
+    fn image(&mut self, image: &ElemImage) {
+        let b = &image.bounds;
+        let mut attrs = String::new();
+        if image.opacity < 1. {
+            let _ = write!(attrs, r#" opacity="{}""#, image.opacity);
+        }
+        let _ = writeln!(
+            self.body,
+            r#"  <image x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="none"{} xlink:href="{}"/>"#,
+            b.tl_coord.x,
+            b.tl_coord.y,
+            b.width,
+            b.height,
+            attrs,
+            escape(image.src())
+        );
+    }
+
+    // grips are an editing aid, not part of the drawing
+    fn handle(&mut self, _center: Coordinate, _color: Color) {}
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn points_attr(points: &[Coordinate]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{},{}", p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn to_svg(scene: &Scene) -> String {
+    let mut painter = SvgPainter::new();
+    for (_, elem) in scene.iter() {
+        elem.draw(&mut painter, Highlight::None);
+    }
+    let (width, height) = scene.canvas_size();
+    painter.finish(width, height)
+}
+
+// -- import
+
+// curves are split into this many straight pieces
+const CURVE_STEPS: usize = 16;
+
+pub struct Import {
+    pub scene: Scene,
+    // what could not be brought over, one line per kind of problem
+    pub warnings: Vec<String>,
+}
+
+fn warn(warnings: &mut Vec<String>, message: String) {
+    if !warnings.contains(&message) {
+        warnings.push(message);
+    }
+}
+
+// an open or closed run of points
+struct SubPath {
+    points: Vec<Coordinate>,
+    // segment end points, without the flattened curve steps
+    anchors: Vec<Coordinate>,
+    // both controls of the segment ending at each anchor after the first,
+    // a line has them on its ends
+    controls: Vec<(Coordinate, Coordinate)>,
+    closed: bool,
+    curves: usize,
+}
+
+fn subpaths(data: &usvg::PathData, ts: &usvg::Transform) -> Vec<SubPath> {
+    let apply = |x: f64, y: f64| {
+        let (x, y) = ts.apply(x, y);
+        coord! {x: x, y: y}
+    };
+    let mut out: Vec<SubPath> = Vec::new();
+    for seg in data.iter() {
+        match *seg {
+            usvg::PathSegment::MoveTo { x, y } => out.push(SubPath {
+                points: vec![apply(x, y)],
+                anchors: vec![apply(x, y)],
+                controls: Vec::new(),
+                closed: false,
+                curves: 0,
+            }),
+            usvg::PathSegment::LineTo { x, y } => {
+                if let Some(sub) = out.last_mut() {
+                    let from = *sub.anchors.last().unwrap();
+                    sub.points.push(apply(x, y));
+                    sub.anchors.push(apply(x, y));
+                    sub.controls.push((from, apply(x, y)));
+                }
+            }
+            usvg::PathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                if let Some(sub) = out.last_mut() {
+                    let p0 = *sub.points.last().unwrap();
+                    let (p1, p2, p3) = (apply(x1, y1), apply(x2, y2), apply(x, y));
+                    for i in 1..=CURVE_STEPS {
+                        let t = i as f64 / CURVE_STEPS as f64;
+                        sub.points.push(cubic_point(p0, p1, p2, p3, t));
+                    }
+                    sub.anchors.push(p3);
+                    sub.controls.push((p1, p2));
+                    sub.curves += 1;
+                }
+            }
+            usvg::PathSegment::ClosePath => {
+                if let Some(sub) = out.last_mut() {
+                    sub.closed = true;
+                }
+            }
+        }
+    }
+    out
+}
+
+// a closed axis-aligned quad becomes a rect
+fn as_rect(sub: &SubPath) -> Option<ElemRect> {
+    let mut points = sub.points.clone();
+    if points.len() == 5 && points[0] == points[4] {
+        points.pop();
+    }
+    if !sub.closed || sub.curves > 0 || points.len() != 4 {
+        return None;
+    }
+    let same = |a: f64, b: f64| (a - b).abs() < 1e-6;
+    let aligned = (0..4).all(|i| {
+        let (a, b) = (points[i], points[(i + 1) % 4]);
+        let horizontal = same(a.y, b.y);
+        let vertical = same(a.x, b.x);
+        // edges alternate between horizontal and vertical
+        if (i % 2 == 0) == same(points[0].y, points[1].y) {
+            horizontal
+        } else {
+            vertical
+        }
+    });
+    if !aligned {
+        return None;
+    }
+    let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+    let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+    let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+    let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+    Some(ElemRect::new(
+        coord! {x: min_x, y: min_y},
+        max_x - min_x,
+        max_y - min_y,
+    ))
+}
+
+// usvg turns <circle> and <ellipse> into four curves between the points
+// where the outline meets its axes
+fn as_ellipse(sub: &SubPath) -> Option<ElemEllipse> {
+    let a = &sub.anchors;
+    if !sub.closed || sub.curves != 4 || a.len() != 5 {
+        return None;
+    }
+    let same = |a: f64, b: f64| (a - b).abs() < 1e-3;
+    let center = coord! {x: (a[0].x + a[2].x) / 2., y: (a[0].y + a[2].y) / 2.};
+    let other = coord! {x: (a[1].x + a[3].x) / 2., y: (a[1].y + a[3].y) / 2.};
+    if !same(center.x, other.x) || !same(center.y, other.y) {
+        return None;
+    }
+    let (rx, ry) = if same(a[0].y, a[2].y) && same(a[1].x, a[3].x) {
+        ((a[0].x - center.x).abs(), (a[1].y - center.y).abs())
+    } else if same(a[0].x, a[2].x) && same(a[1].y, a[3].y) {
+        ((a[1].x - center.x).abs(), (a[0].y - center.y).abs())
+    } else {
+        // rotated, keep the flattened outline
+        return None;
+    };
+    Some(ElemEllipse::new(center, rx, ry))
+}
+
+// curves are kept as they are, a control on the opposite side of its
+// anchor from the other one makes a smooth node
+fn as_bezier(sub: &SubPath) -> ElemBezier {
+    let mut nodes = vec![Node::corner(sub.anchors[0])];
+    for (anchor, (c1, c2)) in sub.anchors[1..].iter().zip(&sub.controls) {
+        if let Some(last) = nodes.last_mut() {
+            last.out_ctrl = *c1;
+        }
+        nodes.push(Node {
+            in_ctrl: *c2,
+            ..Node::corner(*anchor)
+        });
+    }
+    let same = |a: Coordinate, b: Coordinate| (a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6;
+    if sub.closed && nodes.len() > 2 && same(nodes[0].anchor, nodes[nodes.len() - 1].anchor) {
+        let last = nodes.pop().unwrap();
+        nodes[0].in_ctrl = last.in_ctrl;
+    }
+    for node in &mut nodes {
+        let (a, i, o) = (node.anchor, node.in_ctrl, node.out_ctrl);
+        let (ix, iy, ox, oy) = (i.x - a.x, i.y - a.y, o.x - a.x, o.y - a.y);
+        let cross = ix * oy - iy * ox;
+        let lengths = (ix * ix + iy * iy).sqrt() * (ox * ox + oy * oy).sqrt();
+        node.smooth = lengths > 0. && cross.abs() < 1e-3 * lengths && ix * ox + iy * oy < 0.;
+    }
+    ElemBezier::new(nodes, sub.closed)
+}
+
+fn import_path(scene: &mut Scene, path: &usvg::Path, mut style: Style, ts: &usvg::Transform) {
+    // text comes back as glyph outlines, `import_text` reads it instead
+    if path.text_bbox.is_some() {
+        return;
+    }
+    let (sx, sy) = ts.get_scale();
+    style.width *= (sx * sy).sqrt();
+    let mut push = |mut elem: Box<dyn Elem>| {
+        if let Some(elem_style) = elem.style_mut() {
+            *elem_style = style;
+        }
+        scene.push(elem);
+    };
+    for sub in subpaths(&path.data, ts) {
+        if let Some(rect) = as_rect(&sub) {
+            push(Box::new(rect));
+            continue;
+        }
+        if let Some(ellipse) = as_ellipse(&sub) {
+            push(Box::new(ellipse));
+            continue;
+        }
+        if sub.curves > 0 {
+            push(Box::new(as_bezier(&sub)));
+            continue;
+        }
+        let mut points = sub.points;
+        points.dedup();
+        if sub.closed && points.len() > 3 && points.first() == points.last() {
+            points.pop();
+        }
+        match points.len() {
+            0 | 1 => (),
+            2 if !sub.closed => push(Box::new(ElemLine::new(points[0], points[1]))),
+            _ => push(Box::new(ElemPolyline::new(points, sub.closed))),
+        }
+    }
+}
+
+fn usvg_color(color: usvg::Color, opacity: usvg::Opacity) -> Color {
+    let alpha = (opacity.value() * 255.).round() as u8;
+    [color.red, color.green, color.blue, alpha]
+}
+
+// an `opacity` attribute ends up on the groups around a path
+fn group_opacity(node: &usvg::Node) -> f64 {
+    node.ancestors()
+        .filter_map(|n| match &*n.borrow() {
+            usvg::NodeKind::Group(group) => Some(group.opacity.value()),
+            _ => None,
+        })
+        .product()
+}
+
+// the closest `Style` to a path's stroke and fill; without a stroke the
+// outline is fully transparent
+fn path_style(path: &usvg::Path, warnings: &mut Vec<String>) -> Style {
+    let mut style = Style::default();
+    match &path.stroke {
+        Some(stroke) => {
+            match stroke.paint {
+                usvg::Paint::Color(color) => style.stroke = usvg_color(color, stroke.opacity),
+                usvg::Paint::Link(_) => warn(
+                    warnings,
+                    String::from("gradient and pattern strokes were replaced by a solid stroke"),
+                ),
+            }
+            style.width = stroke.width.value();
+            style.dash = match &stroke.dasharray {
+                None => Dash::Solid,
+                Some(dashes) if dashes.len() >= 4 => Dash::DashDot,
+                Some(dashes) if dashes[0] <= style.width * 1.5 => Dash::Dot,
+                Some(_) => Dash::Dash,
+            };
+            style.cap = match stroke.linecap {
+                usvg::LineCap::Butt => Cap::Butt,
+                usvg::LineCap::Round => Cap::Round,
+                usvg::LineCap::Square => Cap::Square,
+            };
+            style.join = match stroke.linejoin {
+                usvg::LineJoin::Miter => Join::Miter,
+                usvg::LineJoin::Round => Join::Round,
+                usvg::LineJoin::Bevel => Join::Bevel,
+            };
+        }
+        None => style.stroke = [0, 0, 0, 0],
+    }
+    match &path.fill {
+        Some(usvg::Fill {
+            paint: usvg::Paint::Color(color),
+            opacity,
+            ..
+        }) => style.fill = Some(usvg_color(*color, *opacity)),
+        Some(_) => warn(
+            warnings,
+            String::from("gradient and pattern fills were dropped"),
+        ),
+        None => (),
+    }
+    style
+}
+
+fn import_image(
+    scene: &mut Scene,
+    warnings: &mut Vec<String>,
+    image: &usvg::Image,
+    ts: &usvg::Transform,
+) {
+    let data = match &image.kind {
+        usvg::ImageKind::PNG(data) | usvg::ImageKind::JPEG(data) | usvg::ImageKind::GIF(data) => {
+            data
+        }
+        usvg::ImageKind::SVG(_) => {
+            warn(warnings, String::from("nested SVG images were skipped"));
+            return;
+        }
+    };
+    if ts.b != 0. || ts.c != 0. {
+        warn(
+            warnings,
+            String::from("rotated or skewed images were placed upright"),
+        );
+    }
+    let r = image.view_box.rect;
+    let (x0, y0) = ts.apply(r.x(), r.y());
+    let (x1, y1) = ts.apply(r.x() + r.width(), r.y() + r.height());
+    let (x, y, w, h) = (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs());
+    let mut elem = match ElemImage::embedded(coord! {x: x, y: y}, data) {
+        Ok(elem) => elem,
+        Err(_) => {
+            warn(warnings, String::from("unreadable images were skipped"));
+            return;
+        }
+    };
+    if image.view_box.aspect.align == usvg::Align::None {
+        elem.bounds = ElemRect::new(coord! {x: x, y: y}, w, h);
+    } else {
+        // centered inside the box, as preserveAspectRatio's default does
+        elem.fit(w, h);
+        elem.bounds.tl_coord = coord! {
+            x: x + (w - elem.bounds.width) / 2.,
+            y: y + (h - elem.bounds.height) / 2.,
+        };
+    }
+    scene.push(Box::new(elem));
+}
+
+// id of the empty path that holds the place of the `n`th <text>
+const TEXT_MARKER: &str = "fltk-demo-text-";
+
+// usvg only keeps text as outlines, so every <text> is swapped for an empty
+// marker path that keeps its place in the drawing order, and is read from
+// the source at that point to stay editable
+fn mark_texts<'a>(
+    source: &str,
+    doc: &'a roxmltree::Document<'a>,
+) -> (String, Vec<roxmltree::Node<'a, 'a>>) {
+    let texts: Vec<_> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("text"))
+        .filter(|n| !n.ancestors().any(|a| a.has_tag_name("defs")))
+        .collect();
+    let mut marked = String::with_capacity(source.len());
+    let mut at = 0;
+    for (i, node) in texts.iter().enumerate() {
+        let range = node.range();
+        marked.push_str(&source[at..range.start]);
+        let _ = write!(
+            marked,
+            r#"<path id="{}{}" d="M0 0h1" fill="none" stroke="none"/>"#,
+            TEXT_MARKER, i
+        );
+        at = range.end;
+    }
+    marked.push_str(&source[at..]);
+    (marked, texts)
+}
+
+pub fn import(text: &str) -> Result<Import, usvg::Error> {
+    use usvg::NodeExt;
+
+    let doc = roxmltree::Document::parse(text).ok();
+    let (source, texts) = match &doc {
+        Some(doc) => mark_texts(text, doc),
+        None => (String::from(text), Vec::new()),
+    };
+
+    let opt = usvg::Options::default();
+    let tree = usvg::Tree::from_str(&source, &opt.to_ref())?;
+
+    let mut scene = Scene::new();
+    let size = tree.svg_node().size;
+    scene.set_canvas_size(size.width().ceil() as u32, size.height().ceil() as u32);
+
+    let mut warnings = Vec::new();
+    for node in tree.root().descendants() {
+        match &*node.borrow() {
+            usvg::NodeKind::Path(path) if path.id.starts_with(TEXT_MARKER) => {
+                let text = path.id[TEXT_MARKER.len()..]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| texts.get(i));
+                if let Some(elem) = text.and_then(|n| import_text(n, &mut warnings)) {
+                    scene.push(Box::new(elem));
+                }
+            }
+            usvg::NodeKind::Path(path) => {
+                let mut style = path_style(path, &mut warnings);
+                style.opacity = group_opacity(&node);
+                import_path(&mut scene, path, style, &node.abs_transform())
+            }
+            usvg::NodeKind::Image(image) => {
+                import_image(&mut scene, &mut warnings, image, &node.abs_transform())
+            }
+            usvg::NodeKind::Group(group)
+                if group.clip_path.is_some()
+                    || group.mask.is_some()
+                    || !group.filter.is_empty() =>
+            {
+                warn(
+                    &mut warnings,
+                    String::from("clip paths, masks and filters were ignored"),
+                );
+            }
+            _ => (),
+        }
+    }
+    Ok(Import { scene, warnings })
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim().strip_prefix('#')?;
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => String::from(hex),
+        _ => return None,
+    };
+    let v = u32::from_str_radix(&hex, 16).ok()?;
+    Some([(v >> 16) as u8, (v >> 8) as u8, v as u8, 0xff])
+}
+
+// the first number of an attribute like "12", "12px" or "12 30"
+fn parse_number(value: &str) -> Option<f64> {
+    let first = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .next()?;
+    first.trim_end_matches("px").parse().ok()
+}
+
+// a presentation property, from the `style` attribute first since it wins
+// over the plain attribute of the same name
+fn property<'a>(node: &roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let declared = node.attribute("style").and_then(|style| {
+        style.split(';').find_map(|decl| {
+            let (key, value) = decl.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    });
+    declared.or_else(|| node.attribute(name))
+}
+
+// a <text> as read from the source; each <tspan> becomes a line. An
+// element reports the text of its first child too, so only text nodes count
+fn import_text(node: &roxmltree::Node, warnings: &mut Vec<String>) -> Option<ElemText> {
+    if node.ancestors().any(|n| n.attribute("transform").is_some()) {
+        warn(warnings, String::from("transforms on text were ignored"));
+    }
+    let number = |name: &str, default: f64| {
+        property(node, name)
+            .and_then(parse_number)
+            .unwrap_or(default)
+    };
+    let mut style = TextStyle {
+        size: number("font-size", 16.),
+        ..TextStyle::default()
+    };
+    let family = property(node, "font-family").unwrap_or_default();
+    style.font = if family.contains("mono") || family.contains("Courier") {
+        FontFamily::Mono
+    } else if family.contains("serif") && !family.contains("sans") {
+        FontFamily::Serif
+    } else {
+        FontFamily::Sans
+    };
+    if let Some(color) = property(node, "fill").and_then(parse_color) {
+        style.color = color;
+    }
+    style.align = match property(node, "text-anchor") {
+        Some("middle") => Align::Center,
+        Some("end") => Align::Right,
+        _ => Align::Left,
+    };
+
+    let spans: Vec<String> = node
+        .children()
+        .filter(|n| n.has_tag_name("tspan"))
+        .map(|n| {
+            n.descendants()
+                .filter(|d| d.is_text())
+                .filter_map(|d| d.text())
+                .collect()
+        })
+        .collect();
+    let words = if spans.is_empty() {
+        node.descendants()
+            .filter(|d| d.is_text())
+            .filter_map(|d| d.text())
+            .collect::<String>()
+    } else {
+        spans.join("\n")
+    };
+    let words = words.trim();
+    if words.is_empty() {
+        return None;
+    }
+
+    // x is where the anchor sits and y the first baseline
+    let (x, y) = (number("x", 0.), number("y", 0.));
+    let mut elem = ElemText::new(coord! {x: x, y: y - style.size * 0.8}, words, style);
+    let (width, _) = elem.size();
+    elem.origin.x -= match style.align {
+        Align::Left => 0.,
+        Align::Center => width / 2.,
+        Align::Right => width,
+    };
+    Some(elem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ElemDoc;
+
+    fn kinds(scene: &Scene) -> Vec<String> {
+        scene
+            .iter()
+            .map(|(_, elem)| {
+                let doc = serde_json::to_value(elem.to_doc()).unwrap();
+                String::from(doc["type"].as_str().unwrap())
+            })
+            .collect()
+    }
+
+    fn close(a: Coordinate, b: Coordinate) -> bool {
+        (a.x - b.x).abs() < 0.01 && (a.y - b.y).abs() < 0.01
+    }
+
+    #[test]
+    fn exports_and_parses_back() {
+        let mut scene = Scene::new();
+        scene.set_canvas_size(300, 200);
+        let styled = Style {
+            fill: Some([0, 0x80, 0, 0xff]),
+            ..Style::solid([0, 0, 0xff, 0xff], 4.)
+        };
+        let mut rect = ElemRect::new(coord! {x: 10., y: 20.}, 60., 40.);
+        rect.style = styled;
+        let elems: Vec<Box<dyn Elem>> = vec![
+            Box::new(ElemLine::new(
+                coord! {x: 5., y: 5.},
+                coord! {x: 120., y: 80.},
+            )),
+            Box::new(rect),
+            Box::new(ElemEllipse::new(coord! {x: 150., y: 100.}, 30., 20.)),
+            Box::new(ElemPolyline::new(
+                vec![
+                    coord! {x: 200., y: 10.},
+                    coord! {x: 280., y: 10.},
+                    coord! {x: 240., y: 70.},
+                ],
+                true,
+            )),
+            Box::new(ElemText::new(
+                coord! {x: 20., y: 150.},
+                "first\nsecond",
+                TextStyle::default(),
+            )),
+        ];
+        for elem in elems {
+            scene.push(elem);
+        }
+
+        let import = import(&to_svg(&scene)).unwrap();
+        let back = import.scene;
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        assert_eq!(back.canvas_size(), (300, 200));
+        assert_eq!(kinds(&back), kinds(&scene));
+        for ((_, a), (_, b)) in scene.iter().zip(back.iter()) {
+            let (va, vb) = (a.get_vertex(), b.get_vertex());
+            assert_eq!(va.len(), vb.len(), "{:?}", b.to_doc());
+            assert!(
+                va.iter().zip(&vb).all(|(a, b)| close(*a, *b)),
+                "{:?}",
+                b.to_doc()
+            );
+        }
+        let (_, rect) = back.iter().nth(1).unwrap();
+        assert_eq!(rect.style(), Some(&styled));
+        let (_, text) = back.iter().last().unwrap();
+        assert_eq!(text.text(), Some("first\nsecond"));
+    }
+
+    #[test]
+    fn keeps_text_in_document_order() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect x="0" y="0" width="40" height="40" fill="#f00"/>
+            <g><text x="5" y="20">over the first</text></g>
+            <rect x="10" y="10" width="40" height="40" fill="#00f"/>
+            <defs><text id="unused">not drawn</text></defs>
+        </svg>"##;
+        let scene = import(svg).unwrap().scene;
+        assert_eq!(kinds(&scene), ["rect", "text", "rect"]);
+    }
+
+    #[test]
+    fn reads_text_properties_from_the_style_attribute() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <text x="50" y="30" fill="#f00" style="font-size: 20px; fill:#0000ff;text-anchor:end">A</text>
+        </svg>"##;
+        let scene = import(svg).unwrap().scene;
+        let (_, elem) = scene.iter().next().unwrap();
+        match elem.to_doc() {
+            ElemDoc::Text { text_style, .. } => {
+                assert_eq!(text_style.size, 20.);
+                assert_eq!(text_style.color, [0, 0, 0xff, 0xff]);
+                assert_eq!(text_style.align, Align::Right);
+            }
+            doc => panic!("not a text: {:?}", doc),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use geo::{coord, Coordinate, EuclideanDistance, Intersects, Point, Rect};
+use rusttype::{point, Scale};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    begin_stroke, document::to_xy, scale_coord, Color, Elem, ElemDoc, Highlight, Painter, Style,
+    GRAB_DISTANCE,
+};
+use crate::fonts;
+
+// line spacing in multiples of the font size
+pub const LINE_HEIGHT: f64 = 1.2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontFamily {
+    Sans,
+    Serif,
+    Mono,
+}
+
+impl FontFamily {
+    pub const ALL: [FontFamily; 3] = [FontFamily::Sans, FontFamily::Serif, FontFamily::Mono];
+
+    // generic CSS family
+    pub fn css(&self) -> &'static str {
+        match self {
+            FontFamily::Sans => "sans-serif",
+            FontFamily::Serif => "serif",
+            FontFamily::Mono => "monospace",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub const ALL: [Align; 3] = [Align::Left, Align::Center, Align::Right];
+
+    // x offset of a line of `width` in a block of `block_width`
+    pub fn offset(&self, width: f64, block_width: f64) -> f64 {
+        match self {
+            Align::Left => 0.,
+            Align::Center => (block_width - width) / 2.,
+            Align::Right => block_width - width,
+        }
+    }
+}
+
+// everything about a text but the words
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    pub font: FontFamily,
+    pub size: f64,
+    pub color: Color,
+    pub align: Align,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            font: FontFamily::Sans,
+            size: 24.,
+            color: [0, 0, 0, 0xff],
+            align: Align::Left,
+        }
+    }
+}
+
+// advance width of one line in the bundled font, which every painter draws
+// text with so the box matches the glyphs
+pub fn measure_line(line: &str, size: f64) -> f64 {
+    let font = fonts::default_font();
+    font.layout(line, Scale::uniform(size as f32), point(0., 0.))
+        .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+        .last()
+        .unwrap_or(0.) as f64
+}
+
+// a block of text; `origin` is the top left of its box
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElemText {
+    pub origin: Coordinate,
+    pub text: String,
+    pub style: TextStyle,
+}
+
+impl ElemText {
+    pub fn new(origin: Coordinate, text: &str, style: TextStyle) -> Self {
+        ElemText {
+            origin,
+            text: String::from(text),
+            style,
+        }
+    }
+
+    pub fn lines(&self) -> Vec<&str> {
+        self.text.split('\n').collect()
+    }
+
+    pub fn line_height(&self) -> f64 {
+        self.style.size * LINE_HEIGHT
+    }
+
+    pub fn size(&self) -> (f64, f64) {
+        let lines = self.lines();
+        let width = lines
+            .iter()
+            .map(|l| measure_line(l, self.style.size))
+            .fold(0., f64::max);
+        (width, lines.len() as f64 * self.line_height())
+    }
+}
+
+impl Elem for ElemText {
+    fn draw(&self, painter: &mut dyn Painter, highlight: Highlight) {
+        let vertex = self.get_vertex();
+        begin_stroke(painter, &vertex, &Style::default(), highlight);
+        if highlight != Highlight::None {
+            let (w, h) = self.size();
+            painter.rect(self.origin, w, h);
+        }
+        painter.text(self);
+    }
+
+    // the corners of the box
+    fn get_vertex(&self) -> Vec<Coordinate> {
+        let (w, h) = self.size();
+        let o = self.origin;
+        vec![
+            o,
+            coord! {x: o.x + w, y: o.y},
+            coord! {x: o.x + w, y: o.y + h},
+            coord! {x: o.x, y: o.y + h},
+        ]
+    }
+
+    fn creating(&mut self, _from_coord: Coordinate, end_coord: Coordinate) {
+        self.origin = end_coord;
+    }
+
+    fn edit_moving(&mut self, from_coord: Coordinate, end_coord: Coordinate) {
+        self.origin.x += end_coord.x - from_coord.x;
+        self.origin.y += end_coord.y - from_coord.y;
+    }
+
+    // dragging a corner scales the font, the opposite corner stays put
+    fn edit_resizing(&mut self, _from_coord: Coordinate, end_coord: Coordinate, drag_vertex: i32) {
+        if !(0..4).contains(&drag_vertex) {
+            return;
+        }
+        let vertex = self.get_vertex();
+        let anchor = vertex[((drag_vertex + 2) % 4) as usize];
+        let (_, h) = self.size();
+        let new_h = (end_coord.y - anchor.y).abs().max(1.);
+        self.style.size = (self.style.size * new_h / h).max(1.);
+        let (w, h) = self.size();
+        self.origin = coord! {
+            x: if drag_vertex == 0 || drag_vertex == 3 { anchor.x - w } else { anchor.x },
+            y: if drag_vertex <= 1 { anchor.y - h } else { anchor.y },
+        };
+    }
+
+    // the font follows the height, lines can't be stretched on their own
+    fn scale(&mut self, origin: Coordinate, _sx: f64, sy: f64) {
+        self.origin = scale_coord(self.origin, origin, sy, sy);
+        self.style.size = (self.style.size * sy).max(1.);
+    }
+
+    fn hover_condition(&self, mouse_point: Point) -> bool {
+        let vertex = self.get_vertex();
+        Rect::new(vertex[0], vertex[2]).intersects(&mouse_point)
+            || vertex
+                .iter()
+                .any(|v| Point::from(*v).euclidean_distance(&mouse_point) < GRAB_DISTANCE)
+    }
+
+    fn text(&self) -> Option<&str> {
+        Some(&self.text)
+    }
+
+    fn set_text(&mut self, text: &str) -> bool {
+        self.text = String::from(text);
+        true
+    }
+
+    fn box_clone(&self) -> Box<dyn Elem> {
+        Box::new(self.clone())
+    }
+
+    fn kind(&self) -> &'static str {
+        "text"
+    }
+
+    fn heap_size(&self) -> usize {
+        self.text.len()
+    }
+
+    fn to_doc(&self) -> ElemDoc {
+        ElemDoc::Text {
+            origin: to_xy(self.origin),
+            text: self.text.clone(),
+            text_style: self.style,
+        }
+    }
+}
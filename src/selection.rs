@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SelectionConstraint {
+    Free,
+    // width : height
+    Ratio { width: f64, height: f64 },
+    // exact size in points, the unit of the capture window and of the saved
+    // image; the native region is this times the display scale
+    Fixed { width: u32, height: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub constraint: SelectionConstraint,
+}
+
+impl SelectionPreset {
+    pub fn builtin() -> Vec<SelectionPreset> {
+        let preset = |name: &str, constraint| SelectionPreset {
+            name: String::from(name),
+            constraint,
+        };
+        vec![
+            preset("Free", SelectionConstraint::Free),
+            preset(
+                "16:9",
+                SelectionConstraint::Ratio {
+                    width: 16.,
+                    height: 9.,
+                },
+            ),
+            preset(
+                "4:3",
+                SelectionConstraint::Ratio {
+                    width: 4.,
+                    height: 3.,
+                },
+            ),
+            preset(
+                "1:1",
+                SelectionConstraint::Ratio {
+                    width: 1.,
+                    height: 1.,
+                },
+            ),
+            preset(
+                "1200x630 pt",
+                SelectionConstraint::Fixed {
+                    width: 1200,
+                    height: 630,
+                },
+            ),
+        ]
+    }
+}
+
+// the x, y, w, h selected by dragging from `anchor` to `cursor`.
+// The rectangle always grows from the anchor towards the cursor.
+pub fn constrain(
+    anchor: (i32, i32),
+    cursor: (i32, i32),
+    constraint: SelectionConstraint,
+) -> (i32, i32, i32, i32) {
+    let dx = cursor.0 - anchor.0;
+    let dy = cursor.1 - anchor.1;
+
+    let (w, h) = match constraint {
+        SelectionConstraint::Free => (dx.abs(), dy.abs()),
+        SelectionConstraint::Ratio { width, height } => {
+            let ratio = if height > 0. { width / height } else { 1. };
+            let (w, h) = (dx.abs() as f64, dy.abs() as f64);
+            // follow whichever side the pointer pulls further
+            if w > h * ratio {
+                (w.round() as i32, (w / ratio).round() as i32)
+            } else {
+                ((h * ratio).round() as i32, h.round() as i32)
+            }
+        }
+        SelectionConstraint::Fixed { width, height } => (width as i32, height as i32),
+    };
+
+    let x = if dx < 0 { anchor.0 - w } else { anchor.0 };
+    let y = if dy < 0 { anchor.1 - h } else { anchor.1 };
+    (x, y, w, h)
+}
+
+// moves (not shrinks) the rectangle back inside `bounds` where it fits
+pub fn clamp_to(rect: (i32, i32, i32, i32), bounds: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+    let (mut x, mut y, w, h) = rect;
+    let (bx, by, bw, bh) = bounds;
+    if w <= bw {
+        x = x.clamp(bx, bx + bw - w);
+    }
+    if h <= bh {
+        y = y.clamp(by, by + bh - h);
+    }
+    (x, y, w, h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_size_ignores_the_drag_distance() {
+        let fixed = SelectionConstraint::Fixed {
+            width: 1200,
+            height: 630,
+        };
+        assert_eq!(constrain((10, 20), (15, 22), fixed), (10, 20, 1200, 630));
+        // a click without a drag already has the full size
+        assert_eq!(constrain((10, 20), (10, 20), fixed), (10, 20, 1200, 630));
+        // dragging up and left grows the other way
+        assert_eq!(
+            constrain((1300, 700), (1290, 690), fixed),
+            (100, 70, 1200, 630)
+        );
+    }
+
+    #[test]
+    fn ratio_follows_the_longer_pull() {
+        let wide = SelectionConstraint::Ratio {
+            width: 16.,
+            height: 9.,
+        };
+        assert_eq!(constrain((0, 0), (160, 10), wide), (0, 0, 160, 90));
+        assert_eq!(constrain((0, 0), (10, 90), wide), (0, 0, 160, 90));
+        assert_eq!(
+            constrain((50, 50), (40, 45), SelectionConstraint::Free),
+            (40, 45, 10, 5)
+        );
+    }
+
+    #[test]
+    fn clamp_moves_but_never_shrinks() {
+        let bounds = (0, 0, 1440, 900);
+        assert_eq!(
+            clamp_to((1000, 600, 1200, 630), bounds),
+            (240, 270, 1200, 630)
+        );
+        // too wide to fit: x is left alone
+        assert_eq!(clamp_to((-5, 10, 2000, 100), bounds), (-5, 10, 2000, 100));
+    }
+}
@@ -0,0 +1,109 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    beautify::{Beautify, BeautifyPreset},
+    export::ExportPreset,
+    selection::SelectionPreset,
+    upload::UploadSettings,
+    watermark::Watermark,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // overrides the locale picked from the environment, e.g. "zh-CN"
+    pub locale: Option<String>,
+    // composite the mouse pointer onto captures
+    pub include_cursor: bool,
+    // name of the beautify preset applied to saved captures, none to save as is
+    pub beautify: Option<String>,
+    // name of the export preset used by save and upload
+    pub export: Option<String>,
+    // where captures are saved, ~/Pictures when unset
+    pub capture_dir: Option<String>,
+    // tables go last, toml can't have plain values after them
+    pub beautify_presets: Vec<BeautifyPreset>,
+    pub export_presets: Vec<ExportPreset>,
+    // extra aspect ratios and sizes for the capture selection
+    pub selection_presets: Vec<SelectionPreset>,
+    pub upload: UploadSettings,
+    pub watermark: Watermark,
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        if let Ok(path) = std::env::var("FLTK_DEMO_SETTINGS") {
+            return PathBuf::from(path);
+        }
+        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+        PathBuf::from(home)
+            .join(".config")
+            .join("fltk-demo")
+            .join("settings.toml")
+    }
+
+    // an absolute directory, the tray may run from anywhere
+    pub fn capture_dir(&self) -> PathBuf {
+        match &self.capture_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+                PathBuf::from(home).join("Pictures")
+            }
+        }
+    }
+
+    pub fn load() -> Self {
+        match fs::read_to_string(Self::path()) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+                eprintln!("invalid settings file, using defaults: {}", err);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    // user presets shadow the builtin ones of the same name
+    pub fn beautify_options(&self) -> Option<Beautify> {
+        let name = self.beautify.as_deref()?;
+        self.beautify_presets
+            .iter()
+            .cloned()
+            .chain(BeautifyPreset::builtin())
+            .find(|preset| preset.name == name)
+            .map(|preset| preset.options)
+    }
+
+    pub fn export_presets(&self) -> Vec<ExportPreset> {
+        let mut presets = self.export_presets.clone();
+        for preset in ExportPreset::builtin() {
+            if !presets.iter().any(|p| p.name == preset.name) {
+                presets.push(preset);
+            }
+        }
+        presets
+    }
+
+    pub fn selection_presets(&self) -> Vec<SelectionPreset> {
+        let mut presets = SelectionPreset::builtin();
+        presets.extend(self.selection_presets.iter().cloned());
+        presets
+    }
+
+    pub fn export_preset(&self, name: Option<&str>) -> Option<ExportPreset> {
+        let name = name.or(self.export.as_deref()).unwrap_or("original");
+        self.export_presets().into_iter().find(|p| p.name == name)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+}
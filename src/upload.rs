@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fmt, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadMode {
+    // multipart/form-data with the image in `field`
+    Multipart,
+    // the image bytes are the request body
+    Raw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    pub url: String,
+    pub mode: UploadMode,
+    pub field: String,
+    // dotted path of the share link in the json response, e.g. "data.links.0"
+    pub link_field: String,
+    pub retries: u32,
+    pub timeout_secs: u64,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        UploadSettings {
+            url: String::new(),
+            mode: UploadMode::Multipart,
+            field: String::from("file"),
+            link_field: String::from("url"),
+            retries: 2,
+            timeout_secs: 30,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    NotConfigured,
+    Transport(String),
+    Status(u16, String),
+    BadResponse(String),
+    MissingLink(String),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::NotConfigured => write!(f, "no upload url in the settings file"),
+            UploadError::Transport(err) => write!(f, "request failed: {}", err),
+            UploadError::Status(code, body) => write!(f, "server answered {}: {}", code, body),
+            UploadError::BadResponse(err) => write!(f, "response is not json: {}", err),
+            UploadError::MissingLink(path) => write!(f, "response has no `{}` field", path),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl UploadError {
+    // worth another attempt
+    fn is_transient(&self) -> bool {
+        match self {
+            UploadError::Transport(_) => true,
+            UploadError::Status(code, _) => *code >= 500 || *code == 429,
+            _ => false,
+        }
+    }
+}
+
+pub fn multipart_body(
+    field: &str,
+    filename: &str,
+    content_type: &str,
+    bytes: &[u8],
+    boundary: &str,
+) -> Vec<u8> {
+    let mut body = Vec::with_capacity(bytes.len() + 256);
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary, field, filename, content_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+// follows a dotted path through objects and arrays
+pub fn extract_link(json: &serde_json::Value, path: &str) -> Option<String> {
+    let mut value = json;
+    for key in path.split('.').filter(|k| !k.is_empty()) {
+        value = match value {
+            serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+            _ => value.get(key)?,
+        };
+    }
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+fn send_once(
+    agent: &ureq::Agent,
+    settings: &UploadSettings,
+    content_type: &str,
+    body: &[u8],
+) -> Result<String, UploadError> {
+    let mut request = agent.post(&settings.url);
+    for (key, value) in &settings.headers {
+        request = request.set(key, value);
+    }
+    // set last so a configured header can't drop the multipart boundary
+    request = request.set("Content-Type", content_type);
+    match request.send_bytes(body) {
+        Ok(response) => response
+            .into_string()
+            .map_err(|err| UploadError::Transport(err.to_string())),
+        Err(ureq::Error::Status(code, response)) => Err(UploadError::Status(
+            code,
+            response.into_string().unwrap_or_default(),
+        )),
+        Err(ureq::Error::Transport(err)) => Err(UploadError::Transport(err.to_string())),
+    }
+}
+
+// posts the image and returns the share link from the response
+pub fn upload(
+    bytes: &[u8],
+    filename: &str,
+    content_type: &str,
+    settings: &UploadSettings,
+) -> Result<String, UploadError> {
+    if settings.url.is_empty() {
+        return Err(UploadError::NotConfigured);
+    }
+
+    let (content_type, body) = match settings.mode {
+        UploadMode::Raw => (content_type.to_string(), bytes.to_vec()),
+        UploadMode::Multipart => {
+            let boundary = format!("fltk-demo-{:016x}", rand::random::<u64>());
+            (
+                format!("multipart/form-data; boundary={}", boundary),
+                multipart_body(&settings.field, filename, content_type, bytes, &boundary),
+            )
+        }
+    };
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(settings.timeout_secs))
+        .build();
+
+    let mut attempt = 0;
+    let text = loop {
+        match send_once(&agent, settings, &content_type, &body) {
+            Ok(text) => break text,
+            Err(err) if err.is_transient() && attempt < settings.retries => {
+                attempt += 1;
+                eprintln!("upload attempt {} failed, retrying: {}", attempt, err);
+                thread::sleep(Duration::from_millis(500 * attempt as u64));
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    let json: serde_json::Value =
+        serde_json::from_str(&text).map_err(|err| UploadError::BadResponse(err.to_string()))?;
+    extract_link(&json, &settings.link_field)
+        .ok_or_else(|| UploadError::MissingLink(settings.link_field.clone()))
+}
+
+// runs `upload` on a worker thread, so retries and timeouts never stall the
+// ui; `done` is called with the result on that thread
+pub fn spawn_upload<F>(
+    bytes: Vec<u8>,
+    filename: String,
+    content_type: String,
+    settings: UploadSettings,
+    done: F,
+) -> thread::JoinHandle<()>
+where
+    F: FnOnce(Result<String, UploadError>) + Send + 'static,
+{
+    thread::spawn(move || done(upload(&bytes, &filename, &content_type, &settings)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    #[derive(Debug)]
+    struct Request {
+        headers: Vec<String>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name).then(|| value.trim())
+            })
+        }
+    }
+
+    // a stand-in image host on localhost: answers each connection with the
+    // next (status, body) and hands back what it received
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_string();
+                    if line.is_empty() {
+                        break;
+                    }
+                    headers.push(line);
+                }
+                let mut request = Request {
+                    headers,
+                    body: Vec::new(),
+                };
+                let len = request
+                    .header("content-length")
+                    .map_or(0, |len| len.parse().unwrap());
+                request.body.resize(len, 0);
+                reader.read_exact(&mut request.body).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn settings(url: String) -> UploadSettings {
+        UploadSettings {
+            url,
+            link_field: String::from("data.links.0"),
+            retries: 1,
+            timeout_secs: 5,
+            ..UploadSettings::default()
+        }
+    }
+
+    #[test]
+    fn multipart_upload_returns_the_link() {
+        let (url, server) = serve(vec![(200, r#"{"data":{"links":["http://host/a.png"]}}"#)]);
+        let mut settings = settings(url);
+        settings
+            .headers
+            .insert(String::from("Authorization"), String::from("Bearer t"));
+        settings
+            .headers
+            .insert(String::from("Content-Type"), String::from("text/plain"));
+        let link = upload(b"PNGDATA", "capture.png", "image/png", &settings).unwrap();
+        assert_eq!(link, "http://host/a.png");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert!(request.headers[0].starts_with("POST /upload "));
+        assert_eq!(request.header("authorization"), Some("Bearer t"));
+        let content_types = request
+            .headers
+            .iter()
+            .filter(|line| line.to_ascii_lowercase().starts_with("content-type:"));
+        assert_eq!(content_types.count(), 1);
+        let content_type = request.header("content-type").unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        assert_eq!(
+            request.body,
+            multipart_body("file", "capture.png", "image/png", b"PNGDATA", boundary)
+        );
+    }
+
+    #[test]
+    fn raw_upload_posts_the_bytes() {
+        let (url, server) = serve(vec![(201, r#"{"data":{"links":["x"]}}"#)]);
+        let settings = UploadSettings {
+            mode: UploadMode::Raw,
+            ..settings(url)
+        };
+        assert_eq!(
+            upload(b"JPEG", "c.jpg", "image/jpeg", &settings).unwrap(),
+            "x"
+        );
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].header("content-type"), Some("image/jpeg"));
+        assert_eq!(requests[0].body, b"JPEG");
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, server) = serve(vec![
+            (503, "busy"),
+            (200, r#"{"data":{"links":["second"]}}"#),
+        ]);
+        let link = upload(b"img", "c.png", "image/png", &settings(url)).unwrap();
+        assert_eq!(link, "second");
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (url, server) = serve(vec![(403, "no")]);
+        match upload(b"img", "c.png", "image/png", &settings(url)) {
+            Err(UploadError::Status(403, body)) => assert_eq!(body, "no"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn missing_link_field() {
+        let (url, server) = serve(vec![(200, r#"{"data":{}}"#)]);
+        assert!(matches!(
+            upload(b"img", "c.png", "image/png", &settings(url)),
+            Err(UploadError::MissingLink(_))
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn spawned_upload_reports_back() {
+        let (url, server) = serve(vec![(200, r#"{"data":{"links":["bg"]}}"#)]);
+        let (tx, rx) = mpsc::channel();
+        let worker = spawn_upload(
+            b"img".to_vec(),
+            String::from("c.png"),
+            String::from("image/png"),
+            settings(url),
+            move |result| tx.send(result).unwrap(),
+        );
+        assert_eq!(rx.recv().unwrap().unwrap(), "bg");
+        worker.join().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn unconfigured_url() {
+        assert!(matches!(
+            upload(b"img", "c.png", "image/png", &UploadSettings::default()),
+            Err(UploadError::NotConfigured)
+        ));
+    }
+
+    #[test]
+    fn extracts_nested_links() {
+        let json: serde_json::Value =
+            serde_json::from_str(r#"{"a":{"b":[{"c":"deep"}],"n":3}}"#).unwrap();
+        assert_eq!(extract_link(&json, "a.b.0.c").as_deref(), Some("deep"));
+        assert_eq!(extract_link(&json, "a.n").as_deref(), Some("3"));
+        assert_eq!(extract_link(&json, "a.missing"), None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::path::PathBuf;
+
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_text_mut, text_size},
+    geometric_transformations::{rotate_about_center, Interpolation},
+};
+use rusttype::Scale;
+use serde::{Deserialize, Serialize};
+
+use crate::fonts;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    pub enabled: bool,
+    // `{author}` and `{date}` are filled in when stamping
+    pub text: String,
+    pub author: Option<String>,
+    // a logo used instead of the text
+    pub image: Option<PathBuf>,
+    pub font_size: f32,
+    pub color: [u8; 4],
+    pub position: Position,
+    pub margin: u32,
+    // 0..=1, multiplied into the stamp alpha
+    pub opacity: f32,
+    // degrees, counter clockwise
+    pub rotation: f32,
+    pub tile: bool,
+    // gap between tiles
+    pub spacing: u32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            enabled: false,
+            text: String::from("CONFIDENTIAL {author} {date}"),
+            author: None,
+            image: None,
+            font_size: 24.,
+            color: [0xd0, 0x10, 0x10, 0xff],
+            position: Position::BottomRight,
+            margin: 16,
+            opacity: 0.5,
+            rotation: 0.,
+            tile: false,
+            spacing: 80,
+        }
+    }
+}
+
+impl Watermark {
+    pub fn expand_text(&self, timestamp: u64) -> String {
+        let author = self
+            .author
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_default();
+        let date = crate::metadata::format_utc(timestamp);
+        self.text
+            .replace("{author}", &author)
+            .replace("{date}", &date[..10])
+            .trim()
+            .to_string()
+    }
+}
+
+// top left corners of every stamp of size `stamp` on a `canvas`
+pub fn placements(canvas: (u32, u32), stamp: (u32, u32), watermark: &Watermark) -> Vec<(i32, i32)> {
+    let (cw, ch) = (canvas.0 as i32, canvas.1 as i32);
+    let (sw, sh) = (stamp.0 as i32, stamp.1 as i32);
+    let margin = watermark.margin as i32;
+
+    if watermark.tile {
+        // a brick pattern: every other row is shifted by half a step
+        let step_x = (sw + watermark.spacing as i32).max(1);
+        let step_y = (sh + watermark.spacing as i32).max(1);
+        let mut out = Vec::new();
+        let mut row = 0;
+        let mut y = -sh / 2;
+        while y < ch {
+            let shift = if row % 2 == 1 { step_x / 2 } else { 0 };
+            let mut x = -sw / 2 + shift - step_x;
+            while x < cw {
+                if x + sw > 0 {
+                    out.push((x, y));
+                }
+                x += step_x;
+            }
+            y += step_y;
+            row += 1;
+        }
+        return out;
+    }
+
+    let left = margin;
+    let center_x = (cw - sw) / 2;
+    let right = cw - sw - margin;
+    let top = margin;
+    let center_y = (ch - sh) / 2;
+    let bottom = ch - sh - margin;
+    let pos = match watermark.position {
+        Position::TopLeft => (left, top),
+        Position::Top => (center_x, top),
+        Position::TopRight => (right, top),
+        Position::Left => (left, center_y),
+        Position::Center => (center_x, center_y),
+        Position::Right => (right, center_y),
+        Position::BottomLeft => (left, bottom),
+        Position::Bottom => (center_x, bottom),
+        Position::BottomRight => (right, bottom),
+    };
+    vec![pos]
+}
+
+// size of the box holding a w*h stamp rotated by `degrees`
+pub fn rotated_size(w: u32, h: u32, degrees: f32) -> (u32, u32) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (w, h) = (w as f32, h as f32);
+    // cos(90) is not quite 0, don't let that round up a whole pixel
+    let up = |v: f32| (v - 1e-3).ceil() as u32;
+    (
+        up(w * cos.abs() + h * sin.abs()),
+        up(w * sin.abs() + h * cos.abs()),
+    )
+}
+
+pub fn text_stamp(text: &str, font_size: f32, color: [u8; 4]) -> RgbaImage {
+    let font = fonts::default_font();
+    let scale = Scale::uniform(font_size);
+    let (w, h) = text_size(scale, font, text);
+    // rendering the coverage on its own avoids dark fringes on the transparent layer
+    let mut mask = GrayImage::new(w.max(1) as u32 + 2, h.max(1) as u32 + 2);
+    draw_text_mut(&mut mask, Luma([255]), 1, 1, scale, font, text);
+    RgbaImage::from_fn(mask.width(), mask.height(), |x, y| {
+        let coverage = mask.get_pixel(x, y)[0] as u32;
+        Rgba([
+            color[0],
+            color[1],
+            color[2],
+            (color[3] as u32 * coverage / 255) as u8,
+        ])
+    })
+}
+
+pub fn rotate_stamp(stamp: &RgbaImage, degrees: f32) -> RgbaImage {
+    if degrees % 360. == 0. {
+        return stamp.clone();
+    }
+    let (w, h) = rotated_size(stamp.width(), stamp.height(), degrees);
+    let mut padded = RgbaImage::new(w.max(stamp.width()), h.max(stamp.height()));
+    let ox = (padded.width() - stamp.width()) / 2;
+    let oy = (padded.height() - stamp.height()) / 2;
+    image::imageops::overlay(&mut padded, stamp, ox as i64, oy as i64);
+    // imageproc rotates clockwise for positive angles
+    rotate_about_center(
+        &padded,
+        -degrees.to_radians(),
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+    )
+}
+
+fn blend(dst: &mut Rgba<u8>, src: &Rgba<u8>, opacity: f32) {
+    let a = src[3] as f32 / 255. * opacity;
+    for i in 0..3 {
+        dst[i] = (src[i] as f32 * a + dst[i] as f32 * (1. - a)).round() as u8;
+    }
+    dst[3] = (a * 255. + dst[3] as f32 * (1. - a)).round() as u8;
+}
+
+pub fn apply(img: &mut RgbaImage, watermark: &Watermark, timestamp: u64) {
+    let stamp = match &watermark.image {
+        Some(path) => match image::open(path) {
+            Ok(logo) => logo.to_rgba8(),
+            Err(err) => {
+                eprintln!("watermark image {}: {}", path.display(), err);
+                return;
+            }
+        },
+        None => text_stamp(
+            &watermark.expand_text(timestamp),
+            watermark.font_size,
+            watermark.color,
+        ),
+    };
+    let stamp = rotate_stamp(&stamp, watermark.rotation);
+    let opacity = watermark.opacity.clamp(0., 1.);
+    let (iw, ih) = img.dimensions();
+
+    for (left, top) in placements((iw, ih), stamp.dimensions(), watermark) {
+        for (x, y, src) in stamp.enumerate_pixels() {
+            let px = left + x as i32;
+            let py = top + y as i32;
+            if px < 0 || py < 0 || px as u32 >= iw || py as u32 >= ih {
+                continue;
+            }
+            blend(img.get_pixel_mut(px as u32, py as u32), src, opacity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(position: Position) -> (i32, i32) {
+        let watermark = Watermark {
+            position,
+            margin: 10,
+            ..Watermark::default()
+        };
+        placements((200, 100), (40, 20), &watermark)[0]
+    }
+
+    #[test]
+    fn anchored_positions() {
+        assert_eq!(at(Position::TopLeft), (10, 10));
+        assert_eq!(at(Position::Top), (80, 10));
+        assert_eq!(at(Position::TopRight), (150, 10));
+        assert_eq!(at(Position::Left), (10, 40));
+        assert_eq!(at(Position::Center), (80, 40));
+        assert_eq!(at(Position::Right), (150, 40));
+        assert_eq!(at(Position::BottomLeft), (10, 70));
+        assert_eq!(at(Position::Bottom), (80, 70));
+        assert_eq!(at(Position::BottomRight), (150, 70));
+    }
+
+    #[test]
+    fn tiles_cover_the_canvas_in_bricks() {
+        let watermark = Watermark {
+            tile: true,
+            spacing: 10,
+            ..Watermark::default()
+        };
+        let tiles = placements((200, 100), (40, 20), &watermark);
+        // rows every 30px starting half a stamp above the canvas
+        let mut rows: Vec<i32> = tiles.iter().map(|t| t.1).collect();
+        rows.dedup();
+        assert_eq!(rows, vec![-10, 20, 50, 80]);
+        // odd rows are shifted by half a step
+        let first = |y: i32| tiles.iter().find(|t| t.1 == y).unwrap().0;
+        assert_eq!(first(-10), -20);
+        assert_eq!(first(20), 5);
+        // every tile touches the canvas
+        for (x, y) in &tiles {
+            assert!(x + 40 > 0 && *x < 200 && y + 20 > 0 && *y < 100);
+        }
+        let row0: Vec<i32> = tiles.iter().filter(|t| t.1 == -10).map(|t| t.0).collect();
+        assert_eq!(row0, vec![-20, 30, 80, 130, 180]);
+    }
+
+    #[test]
+    fn rotated_box() {
+        assert_eq!(rotated_size(40, 20, 0.), (40, 20));
+        assert_eq!(rotated_size(40, 20, 90.), (20, 40));
+        // 45 degrees: both sides are (w + h) / sqrt(2)
+        assert_eq!(rotated_size(40, 20, 45.), (43, 43));
+    }
+
+    #[test]
+    fn text_uses_the_given_time() {
+        let watermark = Watermark {
+            text: String::from("{author} {date}"),
+            author: Some(String::from("ann")),
+            ..Watermark::default()
+        };
+        // 2023-11-14T22:13:20Z
+        assert_eq!(watermark.expand_text(1_700_000_000), "ann 2023-11-14");
+    }
+
+    #[test]
+    fn stamps_only_at_the_placement() {
+        let watermark = Watermark {
+            enabled: true,
+            text: String::from("X"),
+            opacity: 1.,
+            ..Watermark::default()
+        };
+        let mut img = RgbaImage::from_pixel(120, 80, Rgba([255, 255, 255, 255]));
+        apply(&mut img, &watermark, 0);
+        let stamp = text_stamp("X", watermark.font_size, watermark.color);
+        let (left, top) = placements((120, 80), stamp.dimensions(), &watermark)[0];
+        let changed: Vec<(u32, u32)> = img
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0 != [255, 255, 255, 255])
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(!changed.is_empty());
+        for (x, y) in changed {
+            assert!(x as i32 >= left && y as i32 >= top);
+            assert!(x < 120 - watermark.margin && y < 80 - watermark.margin);
+        }
+    }
+}